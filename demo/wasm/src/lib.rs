@@ -48,7 +48,12 @@ fn pack_result(mesh: &Mesh<f64>, elapsed_ms: f64) -> Vec<f32> {
     let vert_count = (vertices.len() / 3) as f32;
     let face_count = (indices.len() / 3) as f32;
 
-    let mut result = vec![vert_count, face_count, elapsed_ms as f32, vertices.len() as f32];
+    let mut result = vec![
+        vert_count,
+        face_count,
+        elapsed_ms as f32,
+        vertices.len() as f32,
+    ];
     result.extend_from_slice(&vertices);
     result.push(indices.len() as f32);
     for idx in &indices {
@@ -222,7 +227,7 @@ mod tests {
     #[wasm_bindgen_test]
     fn test_tessellate_gyroid() {
         console_error_panic_hook::set_once();
-        let result = tessellate_gyroid(3.14, 0.1, 2.0);
+        let result = tessellate_gyroid(std::f64::consts::PI, 0.1, 2.0);
         assert!(
             !result.is_empty(),
             "tessellate_gyroid returned empty result"
@@ -232,7 +237,7 @@ mod tests {
     #[wasm_bindgen_test]
     fn test_tessellate_schwartz_p() {
         console_error_panic_hook::set_once();
-        let result = tessellate_schwartz_p(3.14, 0.1, 2.0);
+        let result = tessellate_schwartz_p(std::f64::consts::PI, 0.1, 2.0);
         assert!(
             !result.is_empty(),
             "tessellate_schwartz_p returned empty result"
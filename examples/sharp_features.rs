@@ -33,7 +33,7 @@ fn main() {
     );
 
     println!("=== Gyroid Surface ===");
-    let gyroid = sdf::Gyroid::new(3.14159, 0.0, 2.0);
+    let gyroid = sdf::Gyroid::new(std::f64::consts::PI, 0.0, 2.0);
     let mut mdc = ManifoldDualContouring::new(&gyroid, 0.08, 0.1);
     let mesh = mdc.tessellate().unwrap();
     println!(
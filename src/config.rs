@@ -0,0 +1,248 @@
+use crate::{BoundingBox, DEFAULT_MAX_RETRIES};
+use nalgebra as na;
+
+/// How the surface is treated where it leaves the sampling
+/// [`region`](TessellationConfig::region), or the bbox of the function if there is no region.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Boundary {
+    /// Cut the surface at the region and return an open mesh. The holes can be found with
+    /// [`Mesh::boundary_loops`](crate::Mesh::boundary_loops). Without a region the grid extends
+    /// beyond the bbox of the function, so surfaces that fill their bbox, like
+    /// [`Gyroid`](crate::sdf::Gyroid), end in a ragged border.
+    #[default]
+    Open,
+    /// Intersect the function with the region, which closes the mesh with caps that lie exactly
+    /// on the walls of the region. Use this for watertight parts cut out of infinite surfaces.
+    /// The function is divided by its [`lipschitz`](crate::ImplicitFunction::lipschitz) bound to
+    /// compare it with the distance to the walls.
+    Capped,
+}
+
+/// What happens when the function evaluates to exactly zero on a grid point.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum ZeroHandling {
+    /// Treat zero as if it were slightly positive, i.e. outside. Grid points on the surface are
+    /// classified consistently without any restarts, and the surface passes through them.
+    #[default]
+    Perturb,
+    /// Abandon the attempt, shift the grid by a fraction of a cell and start over, up to
+    /// [`max_retries`](TessellationConfig::max_retries) times. The vertices never lie exactly on
+    /// grid points, at the cost of repeating all work done so far.
+    Restart,
+}
+
+/// The box within which the vertex of an octree cell is placed.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum QefClamp {
+    /// Keep vertices within their cell. Avoids sliver triangles between neighboring cells, but
+    /// sharp features that pass close to a cell wall are cut off.
+    #[default]
+    Cell,
+    /// Let vertices leave their cell by half its size on each side, so the box is as large as
+    /// the parent cell. Reproduces sharp features more faithfully, at the risk of thin or folded
+    /// triangles.
+    DilatedParent,
+}
+
+/// How the crossing of the surface is searched on grid edges whose ends differ in sign.
+///
+/// All methods keep a bracket around the crossing and stop once it is smaller than the
+/// tolerance, once the value at one of its ends is, or after
+/// [`max_root_iterations`](TessellationConfig::max_root_iterations).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum RootFinder {
+    /// Halve the bracket in each step. Robust, but needs the most evaluations.
+    Bisection,
+    /// False position with Anderson-Björck scaling: when the same end of the bracket is kept
+    /// twice in a row, the value at the other end is scaled down, so that neither end stays
+    /// fixed as with plain regula falsi. Falls back to the Illinois factor of one half.
+    #[default]
+    Illinois,
+    /// Newton steps along the edge, using
+    /// [`value_and_gradient`](crate::ImplicitFunction::value_and_gradient), with bisection
+    /// whenever a step would leave the bracket. Converges in the fewest steps, but only pays off
+    /// if the gradient is cheap, e.g. analytic or from [`sdf::AutoDiff`](crate::sdf::AutoDiff).
+    Newton,
+}
+
+/// Tuning parameters for [`ManifoldDualContouring`](crate::ManifoldDualContouring).
+///
+/// The defaults reproduce the behavior of
+/// [`ManifoldDualContouring::new`](crate::ManifoldDualContouring::new). Each field has a
+/// chainable setter of the same name:
+///
+/// ```rust
+/// use tessellation::{sdf, ManifoldDualContouring, TessellationConfig};
+///
+/// let sphere = sdf::Sphere::new(1.0);
+/// let config = TessellationConfig::default().precision(0.01).simplify(false);
+/// let mut mdc = ManifoldDualContouring::with_config(&sphere, 0.2, 0.1, config);
+/// let mesh = mdc.tessellate().unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct TessellationConfig<S: na::Scalar> {
+    /// How the crossing of the surface on grid edges is searched.
+    pub root_finder: RootFinder,
+    /// Tolerance of root finding relative to the resolution. Root finding stops once the
+    /// bracket is smaller than `absolute_precision + precision * res`.
+    pub precision: S,
+    /// Tolerance of root finding in absolute units, added to the relative
+    /// [`precision`](Self::precision).
+    pub absolute_precision: S,
+    /// Maximum number of steps of root finding per grid edge, each of which evaluates the
+    /// function once.
+    pub max_root_iterations: usize,
+    /// Absolute amount by which the bbox of the function is grown to get the sampling grid.
+    pub bbox_padding: S,
+    /// Additional growth of the sampling grid, in multiples of `res`.
+    pub bbox_padding_cells: S,
+    /// Singular values of the QEF below this fraction of the largest one are truncated, which
+    /// keeps vertices at the mass point of their crossings along directions in which the surface
+    /// is nearly flat. Larger values smooth out shallow creases.
+    pub qef_truncation: S,
+    /// Weight of a penalty on the squared distance between a vertex and the mass point of its
+    /// crossings, per crossing. Zero keeps sharp features, larger values trade them for evenly
+    /// spaced vertices.
+    pub qef_regularization: S,
+    /// Where vertices may be placed relative to their cell.
+    pub qef_clamp: QefClamp,
+    /// Whether to merge vertices in the octree at all.
+    pub simplify: bool,
+    /// Whether merges are only allowed if the merged vertex is 2-manifold.
+    pub manifold_checks: bool,
+    /// Whether to reject merges until the mesh is manifold and each of its connected components
+    /// has the Euler characteristic and number of boundary loops, and thus the genus, of the
+    /// mesh without simplification.
+    /// Rejected merges are counted in
+    /// [`TessellationStats::rejected_collapses`](crate::TessellationStats::rejected_collapses)
+    /// and reported to
+    /// [`TessellationObserver::collapse_rejected`](crate::TessellationObserver::collapse_rejected).
+    /// [`TiledManifoldDualContouring`](crate::TiledManifoldDualContouring), whose tiles do not
+    /// see the whole mesh, fails with
+    /// [`DualContouringError::Unsupported`](crate::DualContouringError::Unsupported) instead.
+    pub preserve_topology: bool,
+    /// What to do when the function evaluates to exactly zero on a grid point.
+    pub zero_handling: ZeroHandling,
+    /// How often to shift the grid and retry after the function evaluated to exactly zero on a
+    /// grid point, with [`ZeroHandling::Restart`].
+    pub max_retries: usize,
+    /// Seed from which the grid offsets for retries are derived. Identical inputs and seeds
+    /// always produce identical meshes.
+    pub seed: u64,
+    /// Only tessellate within this box instead of the bbox of the function.
+    pub region: Option<BoundingBox<S>>,
+    /// What to do where the surface leaves [`region`](Self::region).
+    pub boundary: Boundary,
+}
+
+impl<S: na::Scalar + From<f32>> Default for TessellationConfig<S> {
+    fn default() -> Self {
+        TessellationConfig {
+            root_finder: RootFinder::Illinois,
+            precision: From::from(0.05f32),
+            absolute_precision: From::from(0f32),
+            max_root_iterations: 32,
+            bbox_padding: From::from(1f32),
+            bbox_padding_cells: From::from(1.1f32),
+            qef_truncation: From::from(0.1f32),
+            qef_regularization: From::from(0f32),
+            qef_clamp: QefClamp::Cell,
+            simplify: true,
+            manifold_checks: true,
+            preserve_topology: false,
+            zero_handling: ZeroHandling::Perturb,
+            max_retries: DEFAULT_MAX_RETRIES,
+            seed: 0,
+            region: None,
+            boundary: Boundary::Open,
+        }
+    }
+}
+
+impl<S: na::Scalar> TessellationConfig<S> {
+    /// Set [`root_finder`](Self::root_finder).
+    pub fn root_finder(mut self, root_finder: RootFinder) -> Self {
+        self.root_finder = root_finder;
+        self
+    }
+    /// Set [`precision`](Self::precision).
+    pub fn precision(mut self, precision: S) -> Self {
+        self.precision = precision;
+        self
+    }
+    /// Set [`absolute_precision`](Self::absolute_precision).
+    pub fn absolute_precision(mut self, absolute_precision: S) -> Self {
+        self.absolute_precision = absolute_precision;
+        self
+    }
+    /// Set [`max_root_iterations`](Self::max_root_iterations).
+    pub fn max_root_iterations(mut self, max_root_iterations: usize) -> Self {
+        self.max_root_iterations = max_root_iterations;
+        self
+    }
+    /// Set [`bbox_padding`](Self::bbox_padding).
+    pub fn bbox_padding(mut self, bbox_padding: S) -> Self {
+        self.bbox_padding = bbox_padding;
+        self
+    }
+    /// Set [`bbox_padding_cells`](Self::bbox_padding_cells).
+    pub fn bbox_padding_cells(mut self, bbox_padding_cells: S) -> Self {
+        self.bbox_padding_cells = bbox_padding_cells;
+        self
+    }
+    /// Set [`qef_truncation`](Self::qef_truncation).
+    pub fn qef_truncation(mut self, qef_truncation: S) -> Self {
+        self.qef_truncation = qef_truncation;
+        self
+    }
+    /// Set [`qef_regularization`](Self::qef_regularization).
+    pub fn qef_regularization(mut self, qef_regularization: S) -> Self {
+        self.qef_regularization = qef_regularization;
+        self
+    }
+    /// Set [`qef_clamp`](Self::qef_clamp).
+    pub fn qef_clamp(mut self, qef_clamp: QefClamp) -> Self {
+        self.qef_clamp = qef_clamp;
+        self
+    }
+    /// Set [`simplify`](Self::simplify).
+    pub fn simplify(mut self, simplify: bool) -> Self {
+        self.simplify = simplify;
+        self
+    }
+    /// Set [`manifold_checks`](Self::manifold_checks).
+    pub fn manifold_checks(mut self, manifold_checks: bool) -> Self {
+        self.manifold_checks = manifold_checks;
+        self
+    }
+    /// Set [`preserve_topology`](Self::preserve_topology).
+    pub fn preserve_topology(mut self, preserve_topology: bool) -> Self {
+        self.preserve_topology = preserve_topology;
+        self
+    }
+    /// Set [`zero_handling`](Self::zero_handling).
+    pub fn zero_handling(mut self, zero_handling: ZeroHandling) -> Self {
+        self.zero_handling = zero_handling;
+        self
+    }
+    /// Set [`max_retries`](Self::max_retries).
+    pub fn max_retries(mut self, max_retries: usize) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+    /// Set [`seed`](Self::seed).
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+    /// Set [`region`](Self::region).
+    pub fn region(mut self, region: BoundingBox<S>) -> Self {
+        self.region = Some(region);
+        self
+    }
+    /// Set [`boundary`](Self::boundary).
+    pub fn boundary(mut self, boundary: Boundary) -> Self {
+        self.boundary = boundary;
+        self
+    }
+}
@@ -0,0 +1,568 @@
+use approx::{AbsDiffEq, RelativeEq, UlpsEq};
+use na::{ComplexField, RealField};
+use nalgebra as na;
+use num_traits::{FromPrimitive, Num, One, Signed, Zero};
+use simba::scalar::SubsetOf;
+use simba::simd::SimdValue;
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::{
+    Add, AddAssign, Div, DivAssign, Mul, MulAssign, Neg, Rem, RemAssign, Sub, SubAssign,
+};
+
+/// A dual number for forward mode automatic differentiation in three dimensions.
+///
+/// It carries a value along with its gradient with respect to a point, and implements
+/// [`RealField`], so that a function that is generic over its scalar computes its exact
+/// gradient when evaluated with `Dual`s. See [`sdf::AutoDiff`](crate::sdf::AutoDiff).
+///
+/// Comparisons only look at the value, so that branches take the same path as with plain
+/// scalars. Functions without a derivative, such as `floor` or `signum`, have a zero gradient.
+#[derive(Clone, Copy, Debug)]
+pub struct Dual<S: na::Scalar> {
+    /// The value.
+    pub value: S,
+    /// The gradient of the value.
+    pub gradient: na::Vector3<S>,
+}
+
+impl<S: RealField + Copy> Dual<S> {
+    /// A constant, whose gradient is zero.
+    pub fn constant(value: S) -> Self {
+        Dual {
+            value,
+            gradient: na::Vector3::zeros(),
+        }
+    }
+    /// The coordinates of `p` as variables, i.e. with unit gradients along their axes.
+    pub fn point(p: &na::Point3<S>) -> na::Point3<Self> {
+        na::Point3::new(
+            Dual {
+                value: p.x,
+                gradient: na::Vector3::x(),
+            },
+            Dual {
+                value: p.y,
+                gradient: na::Vector3::y(),
+            },
+            Dual {
+                value: p.z,
+                gradient: na::Vector3::z(),
+            },
+        )
+    }
+    // Applies a function with the given value and derivative at self, using the chain rule.
+    // Constant components stay constant where the derivative is infinite, e.g. for the length
+    // of a zero vector.
+    fn chain(self, value: S, derivative: S) -> Self {
+        Dual {
+            value,
+            gradient: self
+                .gradient
+                .map(|g| if g.is_zero() { g } else { g * derivative }),
+        }
+    }
+}
+
+impl<S: RealField + Copy> From<f32> for Dual<S> {
+    fn from(value: f32) -> Self {
+        Dual::constant(na::convert(value as f64))
+    }
+}
+
+impl<S: RealField + Copy> PartialEq for Dual<S> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value == other.value
+    }
+}
+
+impl<S: RealField + Copy> PartialOrd for Dual<S> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        self.value.partial_cmp(&other.value)
+    }
+}
+
+impl<S: RealField + Copy> fmt::Display for Dual<S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.value)
+    }
+}
+
+impl<S: RealField + Copy> Neg for Dual<S> {
+    type Output = Self;
+    fn neg(self) -> Self {
+        Dual {
+            value: -self.value,
+            gradient: -self.gradient,
+        }
+    }
+}
+
+impl<S: RealField + Copy> Add for Dual<S> {
+    type Output = Self;
+    fn add(self, other: Self) -> Self {
+        Dual {
+            value: self.value + other.value,
+            gradient: self.gradient + other.gradient,
+        }
+    }
+}
+
+impl<S: RealField + Copy> Sub for Dual<S> {
+    type Output = Self;
+    fn sub(self, other: Self) -> Self {
+        Dual {
+            value: self.value - other.value,
+            gradient: self.gradient - other.gradient,
+        }
+    }
+}
+
+impl<S: RealField + Copy> Mul for Dual<S> {
+    type Output = Self;
+    fn mul(self, other: Self) -> Self {
+        Dual {
+            value: self.value * other.value,
+            gradient: self.gradient * other.value + other.gradient * self.value,
+        }
+    }
+}
+
+impl<S: RealField + Copy> Div for Dual<S> {
+    type Output = Self;
+    fn div(self, other: Self) -> Self {
+        let value = self.value / other.value;
+        Dual {
+            value,
+            gradient: (self.gradient - other.gradient * value) / other.value,
+        }
+    }
+}
+
+impl<S: RealField + Copy> Rem for Dual<S> {
+    type Output = Self;
+    fn rem(self, other: Self) -> Self {
+        // a % b = a - trunc(a / b) * b, where the truncated quotient is locally constant.
+        let quotient = (self.value / other.value).trunc();
+        Dual {
+            value: self.value % other.value,
+            gradient: self.gradient - other.gradient * quotient,
+        }
+    }
+}
+
+macro_rules! assign_ops {
+    ($($Trait:ident $method:ident $op:tt;)*) => {$(
+        impl<S: RealField + Copy> $Trait for Dual<S> {
+            fn $method(&mut self, other: Self) {
+                *self = *self $op other;
+            }
+        }
+    )*};
+}
+
+assign_ops! {
+    AddAssign add_assign +;
+    SubAssign sub_assign -;
+    MulAssign mul_assign *;
+    DivAssign div_assign /;
+    RemAssign rem_assign %;
+}
+
+impl<S: RealField + Copy> Zero for Dual<S> {
+    fn zero() -> Self {
+        Dual::constant(S::zero())
+    }
+    fn is_zero(&self) -> bool {
+        self.value.is_zero()
+    }
+}
+
+impl<S: RealField + Copy> One for Dual<S> {
+    fn one() -> Self {
+        Dual::constant(S::one())
+    }
+}
+
+impl<S: RealField + Copy> Num for Dual<S> {
+    type FromStrRadixErr = S::FromStrRadixErr;
+    fn from_str_radix(s: &str, radix: u32) -> Result<Self, Self::FromStrRadixErr> {
+        S::from_str_radix(s, radix).map(Dual::constant)
+    }
+}
+
+impl<S: RealField + Copy> Signed for Dual<S> {
+    fn abs(&self) -> Self {
+        ComplexField::abs(*self)
+    }
+    fn abs_sub(&self, other: &Self) -> Self {
+        if *self <= *other {
+            Self::zero()
+        } else {
+            *self - *other
+        }
+    }
+    fn signum(&self) -> Self {
+        Dual::constant(Signed::signum(&self.value))
+    }
+    fn is_positive(&self) -> bool {
+        self.value.is_positive()
+    }
+    fn is_negative(&self) -> bool {
+        self.value.is_negative()
+    }
+}
+
+impl<S: RealField + Copy> FromPrimitive for Dual<S> {
+    fn from_i64(n: i64) -> Option<Self> {
+        S::from_i64(n).map(Dual::constant)
+    }
+    fn from_u64(n: u64) -> Option<Self> {
+        S::from_u64(n).map(Dual::constant)
+    }
+    fn from_f64(n: f64) -> Option<Self> {
+        S::from_f64(n).map(Dual::constant)
+    }
+}
+
+impl<S: RealField + Copy> AbsDiffEq for Dual<S> {
+    type Epsilon = Self;
+    fn default_epsilon() -> Self {
+        Dual::constant(S::default_epsilon())
+    }
+    fn abs_diff_eq(&self, other: &Self, epsilon: Self) -> bool {
+        self.value.abs_diff_eq(&other.value, epsilon.value)
+    }
+}
+
+impl<S: RealField + Copy> RelativeEq for Dual<S> {
+    fn default_max_relative() -> Self {
+        Dual::constant(S::default_max_relative())
+    }
+    fn relative_eq(&self, other: &Self, epsilon: Self, max_relative: Self) -> bool {
+        self.value
+            .relative_eq(&other.value, epsilon.value, max_relative.value)
+    }
+}
+
+impl<S: RealField + Copy> UlpsEq for Dual<S> {
+    fn default_max_ulps() -> u32 {
+        S::default_max_ulps()
+    }
+    fn ulps_eq(&self, other: &Self, epsilon: Self, max_ulps: u32) -> bool {
+        self.value.ulps_eq(&other.value, epsilon.value, max_ulps)
+    }
+}
+
+impl<S: RealField + Copy> SimdValue for Dual<S> {
+    const LANES: usize = 1;
+    type Element = Self;
+    type SimdBool = bool;
+    fn splat(val: Self) -> Self {
+        val
+    }
+    fn extract(&self, _: usize) -> Self {
+        *self
+    }
+    unsafe fn extract_unchecked(&self, _: usize) -> Self {
+        *self
+    }
+    fn replace(&mut self, _: usize, val: Self) {
+        *self = val
+    }
+    unsafe fn replace_unchecked(&mut self, _: usize, val: Self) {
+        *self = val
+    }
+    fn select(self, cond: bool, other: Self) -> Self {
+        if cond {
+            self
+        } else {
+            other
+        }
+    }
+}
+
+impl<S: RealField + Copy> na::Field for Dual<S> {}
+
+impl<S: RealField + Copy> SubsetOf<Dual<S>> for Dual<S> {
+    fn to_superset(&self) -> Self {
+        *self
+    }
+    fn from_superset_unchecked(element: &Self) -> Self {
+        *element
+    }
+    fn is_in_subset(_: &Self) -> bool {
+        true
+    }
+}
+
+macro_rules! subset_of_dual {
+    ($($t:ty),*) => {$(
+        impl<S: RealField + Copy> SubsetOf<Dual<S>> for $t {
+            fn to_superset(&self) -> Dual<S> {
+                Dual::constant(na::convert(*self as f64))
+            }
+            fn from_superset_unchecked(element: &Dual<S>) -> Self {
+                na::convert_unchecked::<S, f64>(element.value) as $t
+            }
+            fn is_in_subset(element: &Dual<S>) -> bool {
+                element.gradient.iter().all(|g| g.is_zero())
+            }
+        }
+    )*};
+}
+
+subset_of_dual!(f32, f64);
+
+macro_rules! unary {
+    ($($name:ident($v:ident) = $value:expr, $derivative:expr;)*) => {$(
+        fn $name(self) -> Self {
+            let $v = self.value;
+            self.chain($value, $derivative)
+        }
+    )*};
+}
+
+macro_rules! piecewise_constant {
+    ($($name:ident),*) => {$(
+        fn $name(self) -> Self {
+            Dual::constant(self.value.$name())
+        }
+    )*};
+}
+
+impl<S: RealField + Copy> ComplexField for Dual<S> {
+    type RealField = Self;
+
+    fn from_real(re: Self) -> Self {
+        re
+    }
+    fn real(self) -> Self {
+        self
+    }
+    fn imaginary(self) -> Self {
+        Self::zero()
+    }
+    fn modulus(self) -> Self {
+        ComplexField::abs(self)
+    }
+    fn modulus_squared(self) -> Self {
+        self * self
+    }
+    fn argument(self) -> Self {
+        if self.value >= S::zero() {
+            Self::zero()
+        } else {
+            Self::pi()
+        }
+    }
+    fn norm1(self) -> Self {
+        ComplexField::abs(self)
+    }
+    fn scale(self, factor: Self) -> Self {
+        self * factor
+    }
+    fn unscale(self, factor: Self) -> Self {
+        self / factor
+    }
+    piecewise_constant!(floor, ceil, round, trunc);
+    fn fract(self) -> Self {
+        Dual {
+            value: self.value.fract(),
+            gradient: self.gradient,
+        }
+    }
+    fn mul_add(self, a: Self, b: Self) -> Self {
+        self * a + b
+    }
+    fn abs(self) -> Self {
+        if self.value < S::zero() {
+            -self
+        } else {
+            self
+        }
+    }
+    fn hypot(self, other: Self) -> Self {
+        (self * self + other * other).sqrt()
+    }
+    fn recip(self) -> Self {
+        Self::one() / self
+    }
+    fn conjugate(self) -> Self {
+        self
+    }
+    unary! {
+        sin(v) = v.sin(), v.cos();
+        cos(v) = v.cos(), -v.sin();
+        tan(v) = v.tan(), S::one() / (v.cos() * v.cos());
+        asin(v) = v.asin(), S::one() / (S::one() - v * v).sqrt();
+        acos(v) = v.acos(), -S::one() / (S::one() - v * v).sqrt();
+        atan(v) = v.atan(), S::one() / (S::one() + v * v);
+        sinh(v) = v.sinh(), v.cosh();
+        cosh(v) = v.cosh(), v.sinh();
+        tanh(v) = v.tanh(), S::one() - v.tanh() * v.tanh();
+        asinh(v) = v.asinh(), S::one() / (v * v + S::one()).sqrt();
+        acosh(v) = v.acosh(), S::one() / (v * v - S::one()).sqrt();
+        atanh(v) = v.atanh(), S::one() / (S::one() - v * v);
+        log2(v) = v.log2(), S::one() / (v * S::ln_2());
+        log10(v) = v.log10(), S::one() / (v * S::ln_10());
+        ln(v) = v.ln(), S::one() / v;
+        ln_1p(v) = v.ln_1p(), S::one() / (S::one() + v);
+        sqrt(v) = v.sqrt(), S::one() / (v.sqrt() + v.sqrt());
+        exp(v) = v.exp(), v.exp();
+        exp2(v) = v.exp2(), v.exp2() * S::ln_2();
+        exp_m1(v) = v.exp_m1(), v.exp();
+        cbrt(v) = v.cbrt(), S::one() / (v.cbrt() * v.cbrt() * na::convert(3.0));
+    }
+    fn sin_cos(self) -> (Self, Self) {
+        (self.sin(), self.cos())
+    }
+    fn log(self, base: Self) -> Self {
+        self.ln() / base.ln()
+    }
+    fn powi(self, n: i32) -> Self {
+        let derivative = if n == 0 {
+            S::zero()
+        } else {
+            self.value.powi(n - 1) * na::convert(n as f64)
+        };
+        self.chain(self.value.powi(n), derivative)
+    }
+    fn powf(self, n: Self) -> Self {
+        if n.gradient.iter().all(|g| g.is_zero()) {
+            let derivative = if n.value.is_zero() {
+                S::zero()
+            } else {
+                self.value.powf(n.value - S::one()) * n.value
+            };
+            return self.chain(self.value.powf(n.value), derivative);
+        }
+        (n * self.ln()).exp()
+    }
+    fn powc(self, n: Self) -> Self {
+        self.powf(n)
+    }
+    fn is_finite(&self) -> bool {
+        self.value.is_finite() && self.gradient.iter().all(|g| g.is_finite())
+    }
+    fn try_sqrt(self) -> Option<Self> {
+        (self.value >= S::zero()).then(|| self.sqrt())
+    }
+}
+
+macro_rules! constants {
+    ($($name:ident),*) => {$(
+        fn $name() -> Self {
+            Dual::constant(S::$name())
+        }
+    )*};
+}
+
+impl<S: RealField + Copy> RealField for Dual<S> {
+    fn is_sign_positive(&self) -> bool {
+        self.value.is_sign_positive()
+    }
+    fn is_sign_negative(&self) -> bool {
+        self.value.is_sign_negative()
+    }
+    fn copysign(self, sign: Self) -> Self {
+        if self.value.is_sign_negative() == sign.value.is_sign_negative() {
+            self
+        } else {
+            -self
+        }
+    }
+    fn max(self, other: Self) -> Self {
+        if self.value >= other.value {
+            self
+        } else {
+            other
+        }
+    }
+    fn min(self, other: Self) -> Self {
+        if self.value <= other.value {
+            self
+        } else {
+            other
+        }
+    }
+    fn clamp(self, min: Self, max: Self) -> Self {
+        RealField::max(RealField::min(self, max), min)
+    }
+    fn atan2(self, other: Self) -> Self {
+        // d atan2(y, x) = (x dy - y dx) / (x^2 + y^2)
+        let denominator = self.value * self.value + other.value * other.value;
+        Dual {
+            value: self.value.atan2(other.value),
+            gradient: (self.gradient * other.value - other.gradient * self.value) / denominator,
+        }
+    }
+    fn min_value() -> Option<Self> {
+        S::min_value().map(Dual::constant)
+    }
+    fn max_value() -> Option<Self> {
+        S::max_value().map(Dual::constant)
+    }
+    constants!(
+        pi,
+        two_pi,
+        frac_pi_2,
+        frac_pi_3,
+        frac_pi_4,
+        frac_pi_6,
+        frac_pi_8,
+        frac_1_pi,
+        frac_2_pi,
+        frac_2_sqrt_pi,
+        e,
+        log2_e,
+        log10_e,
+        ln_2,
+        ln_10
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Dual;
+    use na::{ComplexField, RealField};
+    use nalgebra as na;
+
+    // Compares the gradient of f at p with central differences.
+    fn assert_gradient(f: impl Fn(na::Point3<Dual<f64>>) -> Dual<f64>, p: na::Point3<f64>) {
+        let result = f(Dual::point(&p));
+        let eps = 1e-6;
+        for i in 0..3 {
+            let mut a = p;
+            let mut b = p;
+            a[i] -= eps;
+            b[i] += eps;
+            let numerical = (f(Dual::point(&b)).value - f(Dual::point(&a)).value) / (2. * eps);
+            assert_relative_eq!(result.gradient[i], numerical, epsilon = 1e-6);
+        }
+    }
+
+    #[test]
+    fn gradients() {
+        let p = na::Point3::new(0.3, -0.7, 0.5);
+        assert_gradient(|p| p.x * p.y + p.z / p.x - p.y % Dual::from(0.4), p);
+        assert_gradient(|p| p.coords.norm(), p);
+        assert_gradient(|p| p.x.sin() * p.y.cos() + p.z.tan(), p);
+        assert_gradient(|p| p.x.asin() + p.y.acos() + p.z.atan(), p);
+        assert_gradient(|p| p.x.sinh() * p.y.cosh() + p.z.tanh(), p);
+        assert_gradient(|p| p.x.exp() + p.z.ln() + p.z.log2() + p.x.log10(), p);
+        assert_gradient(|p| p.z.sqrt() + p.y.cbrt() + p.x.powi(3), p);
+        assert_gradient(|p| p.z.powf(p.x) + p.z.powf(Dual::from(2.5)), p);
+        assert_gradient(|p| p.y.atan2(p.x) + p.x.hypot(p.z), p);
+        assert_gradient(|p| RealField::max(p.x, p.z) - ComplexField::abs(p.y), p);
+    }
+
+    #[test]
+    fn compares_values_only() {
+        let p = Dual::point(&na::Point3::new(1., 1., 2.));
+        assert_eq!(p.x, p.y);
+        assert!(p.x < p.z);
+        assert_eq!(Dual::<f64>::from(1.5).gradient, na::Vector3::zeros());
+        assert_eq!(na::convert::<f64, Dual<f64>>(2.).value, 2.);
+    }
+}
@@ -0,0 +1,96 @@
+use crate::BoundingBox;
+use nalgebra as na;
+
+/// Spatially varying error threshold for simplification, see
+/// [`ManifoldDualContouring::set_error_field`](crate::ManifoldDualContouring::set_error_field).
+///
+/// Any `Fn(&Point3<S>) -> S` that is `Sync` is an error field.
+pub trait ErrorField<S: na::Scalar>: Sync {
+    /// The acceptable error of a merged vertex whose cell is centered at `p`, relative to the
+    /// resolution like the `relative_error` of
+    /// [`ManifoldDualContouring::new`](crate::ManifoldDualContouring::new).
+    fn relative_error(&self, p: &na::Point3<S>) -> S;
+}
+
+impl<S: na::Scalar, F: Fn(&na::Point3<S>) -> S + Sync> ErrorField<S> for F {
+    fn relative_error(&self, p: &na::Point3<S>) -> S {
+        self(p)
+    }
+}
+
+/// An [`ErrorField`] made of boxes with their own error thresholds.
+///
+/// Points in several boxes get the smallest of their thresholds, points outside all boxes the
+/// default.
+///
+/// ```rust
+/// use nalgebra as na;
+/// use tessellation::{sdf, BoundingBox, ErrorRegions, ManifoldDualContouring};
+///
+/// let sphere = sdf::Sphere::new(1.0);
+/// // Keep the top of the sphere detailed.
+/// let regions = ErrorRegions::new(0.5).add(
+///     BoundingBox::new(&na::Point3::new(-1., 0.5, -1.), &na::Point3::new(1., 1., 1.)),
+///     0.0,
+/// );
+/// let mut mdc = ManifoldDualContouring::new(&sphere, 0.1, 0.5);
+/// mdc.set_error_field(&regions);
+/// let mesh = mdc.tessellate().unwrap();
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct ErrorRegions<S: na::Scalar> {
+    default: S,
+    regions: Vec<(BoundingBox<S>, S)>,
+}
+
+impl<S: na::RealField + Copy> ErrorRegions<S> {
+    /// Create a field with the given relative error everywhere.
+    pub fn new(default: S) -> Self {
+        ErrorRegions {
+            default,
+            regions: Vec::new(),
+        }
+    }
+    /// Use the given relative error within `bbox`.
+    pub fn add(mut self, bbox: BoundingBox<S>, relative_error: S) -> Self {
+        self.regions.push((bbox, relative_error));
+        self
+    }
+}
+
+impl<S: na::RealField + Copy> ErrorField<S> for ErrorRegions<S> {
+    fn relative_error(&self, p: &na::Point3<S>) -> S {
+        self.regions
+            .iter()
+            .filter(|(bbox, _)| bbox.contains(p))
+            .map(|&(_, error)| error)
+            .fold(None, |min: Option<S>, error| {
+                Some(min.map_or(error, |min| min.min(error)))
+            })
+            .unwrap_or(self.default)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ErrorField, ErrorRegions};
+    use crate::BoundingBox;
+    use nalgebra as na;
+
+    #[test]
+    fn regions() {
+        let regions = ErrorRegions::new(0.5)
+            .add(
+                BoundingBox::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(2., 2., 2.)),
+                0.2,
+            )
+            .add(
+                BoundingBox::new(&na::Point3::new(1., 1., 1.), &na::Point3::new(3., 3., 3.)),
+                0.1,
+            );
+        assert_eq!(regions.relative_error(&na::Point3::new(-1., 0., 0.)), 0.5);
+        assert_eq!(regions.relative_error(&na::Point3::new(0.5, 0.5, 0.5)), 0.2);
+        assert_eq!(regions.relative_error(&na::Point3::new(1.5, 1.5, 1.5)), 0.1);
+        assert_eq!(regions.relative_error(&na::Point3::new(2.5, 2.5, 2.5)), 0.1);
+    }
+}
@@ -28,7 +28,7 @@
 //!   fn value(&self, p: &na::Point3<f64>) -> f64 {
 //!     return na::Vector3::new(p.x, p.y, p.z).norm() - 1.0;
 //!   }
-//!   fn normal(&self, p: &na::Point3<f64>) -> na::Vector3<f64> {
+//!   fn normal(&self, p: &na::Point3<f64>, _epsilon: f64) -> na::Vector3<f64> {
 //!     return na::Vector3::new(p.x, p.y, p.z).normalize();
 //!   }
 //! }
@@ -44,33 +44,156 @@ use std::fmt::Debug;
 
 mod bitset;
 mod bounding_box;
+mod cancellation;
 mod cell_configs;
+mod config;
+mod dual;
+mod error_field;
+mod lod;
 mod manifold_dual_contouring;
 mod mesh;
+mod observer;
 mod plane;
 mod qef;
+mod root_finder;
 /// Composable SDF primitives and CSG operations implementing [`ImplicitFunction`].
 pub mod sdf;
+mod sink;
+mod tiled;
+mod topology;
 mod vertex_index;
 
 pub use self::bounding_box::BoundingBox;
-pub use self::manifold_dual_contouring::ManifoldDualContouring;
+pub use self::cancellation::CancellationToken;
+pub use self::config::{Boundary, QefClamp, RootFinder, TessellationConfig, ZeroHandling};
+pub use self::dual::Dual;
+pub use self::error_field::{ErrorField, ErrorRegions};
+pub use self::lod::{LevelOfDetail, VertexOctree};
+pub use self::manifold_dual_contouring::{
+    DualContouringError, ManifoldDualContouring, DEFAULT_MAX_RETRIES,
+};
 pub use self::mesh::Mesh;
+pub use self::observer::{Stage, StageEvent, TessellationObserver, TessellationStats};
+pub use self::sink::{MeshSink, PlyWriter, StlWriter};
+pub use self::tiled::{TileMesh, TileStitcher, TiledManifoldDualContouring};
 pub use nalgebra::RealField;
 
+// Tessellators and their results can be shared with and moved to other threads, e.g. to
+// tessellate many parts concurrently in a thread pool.
+fn assert_send_sync<T: Send + Sync>() {}
+const _: fn() = || {
+    assert_send_sync::<ManifoldDualContouring<'static, f32>>();
+    assert_send_sync::<ManifoldDualContouring<'static, f64>>();
+    assert_send_sync::<TiledManifoldDualContouring<'static, f64>>();
+    assert_send_sync::<TileMesh<f64>>();
+    assert_send_sync::<TileStitcher<f64>>();
+    assert_send_sync::<Mesh<f64>>();
+    assert_send_sync::<TessellationStats>();
+    assert_send_sync::<DualContouringError>();
+    assert_send_sync::<Box<dyn ImplicitFunction<f64>>>();
+};
+
 /// Trait to be implemented by functions that should be tessellated.
-pub trait ImplicitFunction<S: Debug + RealField> {
+///
+/// Functions are evaluated from several threads at once, hence the `Sync` bound. They are also
+/// `Send`, so that boxed functions can be handed to worker threads.
+pub trait ImplicitFunction<S: Debug + RealField>: Send + Sync {
     /// Return a Bounding Box, which is essential, so the algorithm knows where to search for
     /// surfaces.
     fn bbox(&self) -> &BoundingBox<S>;
     /// Evaluate the function on p and return the value. A value of zero signifies that p is on the
     /// surface to be tessellated. A negative value means p in inside the object. A positive value
     /// means p is outside the object.
-    /// The magnitude of value must be continuous. Furthermore value has to be equal or greater
-    /// than the euclidean distance between p and the surface.
+    /// The value must be continuous. Unless [`value_interval`](Self::value_interval) bounds it,
+    /// its magnitude must not exceed [`lipschitz`](Self::lipschitz) times the euclidean distance
+    /// between p and the surface, or the sampler may skip parts of the surface.
     fn value(&self, p: &na::Point3<S>) -> S;
     /// Compute the normal of the function at p.
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S>;
+    ///
+    /// `epsilon` is the offset for approximating derivatives numerically. Tessellators pass a
+    /// thousandth of their resolution. Implementations that know the normal ignore it.
+    ///
+    /// The default normalizes the gradient of
+    /// [`value_and_gradient`](Self::value_and_gradient). Override it if the normal is cheaper to
+    /// compute on its own, and check the result with [`sdf::check_normals`].
+    fn normal(&self, p: &na::Point3<S>, epsilon: S) -> na::Vector3<S> {
+        self.value_and_gradient(p, epsilon).1.normalize()
+    }
+    /// Evaluate the function and its gradient on p in one call.
+    ///
+    /// The default approximates the gradient of [`value`](Self::value) with central
+    /// differences with offset `epsilon`, which costs six more evaluations. Override it if the
+    /// gradient is known, or wrap a function that is generic over its scalar in
+    /// [`sdf::AutoDiff`] to get the exact gradient.
+    fn value_and_gradient(&self, p: &na::Point3<S>, epsilon: S) -> (S, na::Vector3<S>) {
+        (self.value(p), central_differences(self, p, epsilon))
+    }
+    /// Evaluate the function on all points and store the results in `values`, which has the same
+    /// length as `points`. The default calls [`value`](Self::value) for each point. Functions
+    /// with a high cost per call, such as neural or mesh distance fields, can override it to
+    /// process the points together.
+    fn value_batch(&self, points: &[na::Point3<S>], values: &mut [S]) {
+        debug_assert_eq!(points.len(), values.len());
+        for (p, v) in points.iter().zip(values.iter_mut()) {
+            *v = self.value(p);
+        }
+    }
+    /// Compute the normals at all points and store them in `normals`, which has the same length
+    /// as `points`. The default calls [`normal`](Self::normal) for each point.
+    fn normal_batch(&self, points: &[na::Point3<S>], normals: &mut [na::Vector3<S>], epsilon: S) {
+        debug_assert_eq!(points.len(), normals.len());
+        for (p, n) in points.iter().zip(normals.iter_mut()) {
+            *n = self.normal(p, epsilon.clone());
+        }
+    }
+    /// Return a lower and an upper bound of the values within `bbox`.
+    ///
+    /// The sampler skips boxes whose interval does not contain zero, and subdivides all others,
+    /// which is exact for functions that are not distance bounds. The default returns the
+    /// interval of all finite values, which means that no bound is known. Infinite or NaN bounds,
+    /// and bounds beyond half the largest finite value, are taken to mean the same. The sampler
+    /// then relies on [`lipschitz`](Self::lipschitz).
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let _ = bbox;
+        unbounded_interval()
+    }
+    /// Return an upper bound of the magnitude of the gradient, i.e. how much faster than the
+    /// distance to the surface the value may change. The default of one suits distance fields.
+    /// Use [`sdf::Normalize`] to estimate it for other functions.
+    fn lipschitz(&self) -> S {
+        S::one()
+    }
+}
+
+// Approximates the gradient of f at p with central differences.
+pub(crate) fn central_differences<S, F>(f: &F, p: &na::Point3<S>, epsilon: S) -> na::Vector3<S>
+where
+    S: Debug + RealField,
+    F: ImplicitFunction<S> + ?Sized,
+{
+    let mut gradient = na::Vector3::zeros();
+    for i in 0..3 {
+        let mut delta = na::Vector3::zeros();
+        delta[i] = epsilon.clone();
+        gradient[i] =
+            (f.value(&(p + &delta)) - f.value(&(p - &delta))) / (epsilon.clone() + epsilon.clone());
+    }
+    gradient
+}
+
+// The interval of all finite values, returned by functions that cannot bound their values.
+fn unbounded_interval<S: RealField>() -> (S, S) {
+    let max = S::max_value().expect("scalar type without a maximum");
+    (-max.clone(), max)
+}
+
+// Whether an interval from value_interval carries no bound. Arithmetic on the interval of all
+// finite values may overflow to infinity, give NaN or shrink it a little, so any bound beyond
+// half the largest finite value counts as unbounded.
+pub(crate) fn is_unbounded<S: RealField>(lower: &S, upper: &S) -> bool {
+    let (_, max) = unbounded_interval::<S>();
+    let limit = max / (S::one() + S::one());
+    !(*lower > -limit.clone() && *upper < limit)
 }
 
 impl<S: Debug + RealField> ImplicitFunction<S> for Box<dyn ImplicitFunction<S>> {
@@ -80,8 +203,23 @@ impl<S: Debug + RealField> ImplicitFunction<S> for Box<dyn ImplicitFunction<S>>
     fn value(&self, p: &na::Point3<S>) -> S {
         (**self).value(p)
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
-        (**self).normal(p)
+    fn normal(&self, p: &na::Point3<S>, epsilon: S) -> na::Vector3<S> {
+        (**self).normal(p, epsilon)
+    }
+    fn value_and_gradient(&self, p: &na::Point3<S>, epsilon: S) -> (S, na::Vector3<S>) {
+        (**self).value_and_gradient(p, epsilon)
+    }
+    fn value_batch(&self, points: &[na::Point3<S>], values: &mut [S]) {
+        (**self).value_batch(points, values)
+    }
+    fn normal_batch(&self, points: &[na::Point3<S>], normals: &mut [na::Vector3<S>], epsilon: S) {
+        (**self).normal_batch(points, normals, epsilon)
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        (**self).value_interval(bbox)
+    }
+    fn lipschitz(&self) -> S {
+        (**self).lipschitz()
     }
 }
 
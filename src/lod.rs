@@ -0,0 +1,73 @@
+use crate::{
+    mesh::Mesh, observer::TessellationStats, sink::MeshSink, AsUSize, DualContouringError,
+    ManifoldDualContouring, RealField,
+};
+use num_traits::Float;
+
+/// Selects which nodes of a [`VertexOctree`] become the vertices of an extracted mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum LevelOfDetail<S> {
+    /// Merge cells as long as the error of the merged vertex stays below this threshold,
+    /// relative to the resolution. This is the `relative_error` of
+    /// [`ManifoldDualContouring::new`].
+    RelativeError(S),
+    /// Merge cells up to this octree level regardless of the error, where level 0 are the
+    /// cells of the grid and each level doubles the cell size. Cells whose merge would not be
+    /// 2-manifold stay finer, unless manifold checks are disabled.
+    Depth(usize),
+    /// Choose the error threshold whose mesh has the number of triangles closest to this
+    /// budget. Merges are still only allowed if they are 2-manifold and, with topology
+    /// preservation, keep the topology, so coarse budgets may not be reached.
+    FaceBudget(usize),
+}
+
+/// The vertex octree of a tessellation, from which meshes with different levels of detail can be
+/// extracted without sampling the function again.
+///
+/// Returned by [`ManifoldDualContouring::build_octree`], which solves the QEFs of all levels.
+///
+/// ```rust
+/// use tessellation::{sdf, LevelOfDetail, ManifoldDualContouring};
+///
+/// let sphere = sdf::Sphere::new(1.0);
+/// let mut mdc = ManifoldDualContouring::new(&sphere, 0.05, 0.0);
+/// let octree = mdc.build_octree().unwrap();
+/// let lods: Vec<_> = [0.0, 0.1, 0.5]
+///     .iter()
+///     .map(|&error| octree.extract(LevelOfDetail::RelativeError(error)))
+///     .collect();
+/// assert!(lods[0].faces.len() > lods[2].faces.len());
+/// ```
+pub struct VertexOctree<'m, 'a, S: RealField> {
+    pub(crate) mdc: &'m ManifoldDualContouring<'a, S>,
+    pub(crate) stats: TessellationStats,
+}
+
+impl<S: From<f32> + RealField + Float + AsUSize> VertexOctree<'_, '_, S> {
+    /// Number of octree levels that mesh vertices can come from. [`LevelOfDetail::Depth`]
+    /// values from 0 to `depth() - 1` give different meshes.
+    pub fn depth(&self) -> usize {
+        self.mdc.octree_depth()
+    }
+    /// Statistics about building the octree. Vertex and face counts are zero, since no mesh was
+    /// generated.
+    pub fn stats(&self) -> &TessellationStats {
+        &self.stats
+    }
+    /// Extract the mesh for the given level of detail.
+    pub fn extract(&self, lod: LevelOfDetail<S>) -> Mesh<S> {
+        let mut mesh = Mesh::default();
+        self.extract_into(lod, &mut mesh)
+            .expect("collecting a mesh cannot fail");
+        mesh
+    }
+    /// Extract the mesh for the given level of detail and pass its vertices and faces to
+    /// `sink`. Returns the number of vertices and faces.
+    pub fn extract_into(
+        &self,
+        lod: LevelOfDetail<S>,
+        sink: &mut dyn MeshSink<S>,
+    ) -> Result<(usize, usize), DualContouringError> {
+        self.mdc.extract_into(lod, sink)
+    }
+}
@@ -1,9 +1,18 @@
 use crate::{
     bitset::BitSet,
+    cancellation::CancellationToken,
     cell_configs::CELL_CONFIGS,
+    config::{Boundary, QefClamp, TessellationConfig, ZeroHandling},
+    error_field::ErrorField,
+    is_unbounded,
+    lod::{LevelOfDetail, VertexOctree},
     mesh::Mesh,
+    observer::{Stage, StageEvent, TessellationObserver, TessellationStats},
     plane::Plane,
-    qef,
+    qef, root_finder,
+    sink::MeshSink,
+    tiled::{TileMesh, TileVertex},
+    topology::Topology,
     vertex_index::{offset, Index, VarIndex, VertexIndex, EDGES_ON_FACE},
     AsUSize, BoundingBox, ImplicitFunction, RealField,
 };
@@ -12,14 +21,27 @@ use num_traits::Float;
 #[cfg(not(target_arch = "wasm32"))]
 use rayon::prelude::*;
 use std::{
-    cell::{Cell, RefCell},
     cmp,
     collections::{BTreeSet, HashMap},
-    error, fmt,
+    error, fmt, io,
     sync::LazyLock,
 };
 
-const PRECISION: f32 = 0.05;
+// Number of edges whose faces are computed in parallel before they are passed to the sink.
+const QUAD_CHUNK_SIZE: usize = 1 << 14;
+
+// Number of edge crossings whose normals are requested from the function at once.
+const NORMAL_BATCH_SIZE: usize = 256;
+
+// Distance of cap vertices from the walls of the region below which they are moved onto the
+// walls, relative to the cell size.
+const SNAP_TOLERANCE: f32 = 1e-2;
+
+// Offset of numerically approximated normals, relative to the resolution.
+const NORMAL_EPSILON: f32 = 1e-3;
+
+// Rough share of the total runtime of each stage, in pipeline order.
+const STAGE_WEIGHTS: [f32; 7] = [0.35, 0.05, 0.2, 0.1, 0.05, 0.1, 0.15];
 
 //  Edge indexes
 //
@@ -37,7 +59,7 @@ const PRECISION: f32 = 0.05;
 //
 // Point o is the reference point of the current cell.
 // All edges go from lower indexes to higher indexes.
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub enum Edge {
     A = 0,
     B = 1,
@@ -110,9 +132,29 @@ static OUTSIDE_EDGES_PER_CORNER: LazyLock<[BitSet; 8]> = LazyLock::new(|| {
     ]
 });
 
+/// Number of times tessellate shifts the grid and retries after hitting an exact zero, with
+/// [`ZeroHandling::Restart`](crate::ZeroHandling::Restart).
+pub const DEFAULT_MAX_RETRIES: usize = 10;
+
+/// Errors that can occur during tessellation.
 #[derive(Debug)]
 pub enum DualContouringError {
+    /// The function evaluated to exactly zero on a grid point.
     HitZero(String),
+    /// The function has no surface within its bounding box.
+    EmptySurface,
+    /// The bounding box or resolution is degenerate or not finite.
+    DegenerateBoundingBox(String),
+    /// The function returned NaN.
+    NaNValue(String),
+    /// Every retry hit a zero value. Contains the number of attempts made.
+    RetriesExhausted(usize),
+    /// Tessellation was aborted through a [`CancellationToken`].
+    Cancelled,
+    /// The [`MeshSink`] failed to write the mesh.
+    Io(io::Error),
+    /// The configuration requests something the tessellator cannot do.
+    Unsupported(String),
 }
 
 impl error::Error for DualContouringError {}
@@ -121,63 +163,150 @@ impl fmt::Display for DualContouringError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match *self {
             DualContouringError::HitZero(ref s) => write!(f, "Hit zero value for {}", s),
+            DualContouringError::EmptySurface => write!(f, "No surface found within bbox"),
+            DualContouringError::DegenerateBoundingBox(ref s) => {
+                write!(f, "Degenerate sampling grid: {}", s)
+            }
+            DualContouringError::NaNValue(ref s) => write!(f, "Got NaN value for {}", s),
+            DualContouringError::RetriesExhausted(n) => {
+                write!(f, "Giving up after {} attempts", n)
+            }
+            DualContouringError::Cancelled => write!(f, "Tessellation was cancelled"),
+            DualContouringError::Io(ref e) => write!(f, "Failed to write mesh: {}", e),
+            DualContouringError::Unsupported(ref s) => write!(f, "Unsupported option: {}", s),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Vertex<S: RealField> {
     index: Index,
-    qef: RefCell<qef::Qef<S>>,
+    qef: qef::Qef<S>,
     neighbors: [Vec<VarIndex>; 6],
-    parent: Cell<Option<usize>>,
+    parent: Option<usize>,
     children: Vec<usize>,
-    mesh_index: Cell<Option<usize>>,
     edge_intersections: [u32; 12],
     euler_characteristic: i32,
+    face_contours: [FaceContour; 6],
+    // Whether this vertex and all its descendants are 2-manifold.
+    manifold: bool,
 }
 
-impl<S: RealField> Clone for Vertex<S> {
-    fn clone(&self) -> Vertex<S> {
-        Vertex {
-            index: self.index,
-            qef: self.qef.clone(),
-            neighbors: [
-                self.neighbors[0].clone(),
-                self.neighbors[1].clone(),
-                self.neighbors[2].clone(),
-                self.neighbors[3].clone(),
-                self.neighbors[4].clone(),
-                self.neighbors[5].clone(),
-            ],
-            parent: self.parent.clone(),
-            children: self.children.clone(),
-            mesh_index: self.mesh_index.clone(),
-            edge_intersections: self.edge_intersections,
-            euler_characteristic: self.euler_characteristic,
-        }
+impl<S: RealField> Vertex<S> {
+    // The criterion of Manifold Dual Contouring for replacing the children by this vertex: the
+    // surface within the cell is a disk, and its intersections with each face and each edge of
+    // the cell are disks as well, i.e. a single curve or point, or empty.
+    fn is_2manifold(&self) -> bool {
+        self.euler_characteristic == 1
+            && self.edge_intersections.iter().all(|&n| n <= 1)
+            && self
+                .face_contours
+                .iter()
+                .all(|contour| !contour.tangled && contour.loops == 0 && contour.paths.len() <= 1)
     }
 }
 
-impl<S: RealField> Vertex<S> {
-    fn is_2manifold(&self) -> bool {
-        if self.euler_characteristic != 1 {
-            return false;
+// The curves in which the surface of a vertex meets one face of its cell.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct FaceContour {
+    // Curves between two edges of the face, as edge numbers of the cell.
+    paths: Vec<[usize; 2]>,
+    // Number of closed curves within the face.
+    loops: u32,
+    // Set if the curves could not be traced, because an edge was crossed more than once.
+    tangled: bool,
+}
+
+// A corner of a face before the vertices are numbered.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Corner {
+    // Layer and index of a node of the vertex octree.
+    Node(usize, usize),
+    // A cell of a neighboring tile.
+    Neighbor(VertexIndex),
+}
+
+// Decides how far lookup_vertex merges cells up the octree.
+#[derive(Clone, Copy)]
+struct Collapse<'f, S: RealField> {
+    // Merge only while the error of the merged vertex does not exceed this.
+    error: S,
+    // Replaces error by the threshold at the center of the merged cell.
+    field: Option<&'f dyn ErrorField<S>>,
+    // Octree layer above which cells are never merged.
+    max_layer: usize,
+    // Nodes per layer that may not replace their children.
+    rejected: Option<&'f [Vec<bool>]>,
+}
+
+// Per layer, whether each node may not replace its children.
+type RejectedNodes = Vec<Vec<bool>>;
+
+// A candidate threshold of the face budget search and the face count of its mesh.
+struct BudgetProbe {
+    // Index of the threshold among the candidates.
+    index: usize,
+    faces: usize,
+    rejected: Option<RejectedNodes>,
+}
+
+// Numbers the octree nodes in the order in which the faces first use them, which does not
+// depend on how the faces were computed in parallel.
+struct NodeNumbering {
+    indexes: Vec<Vec<Option<usize>>>,
+    nodes: Vec<(usize, usize)>,
+}
+
+impl NodeNumbering {
+    fn new<S: RealField>(octtree: &[Vec<Vertex<S>>]) -> Self {
+        NodeNumbering {
+            indexes: octtree
+                .iter()
+                .map(|layer| vec![None; layer.len()])
+                .collect(),
+            nodes: Vec::new(),
         }
-        for edges_on_face in EDGES_ON_FACE.iter() {
-            let mut sum = 0;
-            for edge in *edges_on_face {
-                sum += self.edge_intersections[edge];
-            }
-            if sum != 0 && sum != 2 {
-                return false;
-            }
+    }
+    fn number(&mut self, layer: usize, index: usize) -> usize {
+        let nodes = &mut self.nodes;
+        *self.indexes[layer][index].get_or_insert_with(|| {
+            nodes.push((layer, index));
+            nodes.len() - 1
+        })
+    }
+}
+
+// Forwards vertices and faces to a MeshSink and counts them.
+struct MeshOutput<'s, S> {
+    sink: &'s mut dyn MeshSink<S>,
+    vertices: usize,
+    faces: usize,
+}
+
+impl<'s, S> MeshOutput<'s, S> {
+    fn new(sink: &'s mut dyn MeshSink<S>) -> Self {
+        MeshOutput {
+            sink,
+            vertices: 0,
+            faces: 0,
         }
-        true
+    }
+    // Returns the index of the new vertex.
+    fn add_vertex(&mut self, vertex: [S; 3]) -> Result<usize, DualContouringError> {
+        self.sink
+            .add_vertex(vertex)
+            .map_err(DualContouringError::Io)?;
+        self.vertices += 1;
+        Ok(self.vertices - 1)
+    }
+    fn add_face(&mut self, face: [usize; 3]) -> Result<(), DualContouringError> {
+        self.sink.add_face(face).map_err(DualContouringError::Io)?;
+        self.faces += 1;
+        Ok(())
     }
 }
 
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
 pub struct EdgeIndex {
     edge: Edge,
     index: Index,
@@ -192,19 +321,108 @@ impl EdgeIndex {
     }
 }
 
+// The cells [cells[0], cells[1]) of the grid that belong to a tile. Simplification is limited to
+// `levels` octree levels, so that no vertex is merged with vertices of other tiles.
+#[derive(Clone, Debug)]
+struct TileRange {
+    cells: [Index; 2],
+    levels: usize,
+}
+
 /// Struct containing all the intermediary state for the different stages of tessellation.
 #[derive(Clone)]
 pub struct ManifoldDualContouring<'a, S: RealField> {
     function: &'a dyn ImplicitFunction<S>,
+    // The first grid point of the current attempt, which is base_origin shifted by set_attempt.
     origin: na::Point3<S>,
+    base_origin: na::Point3<S>,
     dim: [usize; 3],
-    mesh: RefCell<Mesh<S>>,
     res: S,
     error: S,
     value_grid: HashMap<Index, S>,
-    edge_grid: RefCell<HashMap<EdgeIndex, Plane<S>>>,
+    edge_grid: HashMap<EdgeIndex, Plane<S>>,
     vertex_octtree: Vec<Vec<Vertex<S>>>,
     vertex_index_map: HashMap<VertexIndex, usize>,
+    config: TessellationConfig<S>,
+    // The function is intersected with this box.
+    cap: Option<BoundingBox<S>>,
+    // Divides the function by its Lipschitz constant when it is intersected with the capping box,
+    // so that both are lower bounds of the distance and can be compared.
+    cap_scale: S,
+    // Whether the surface is cut open at the end of the grid.
+    clip: bool,
+    // Bound of the gradient magnitude of the function, including the capping box.
+    lipschitz: S,
+    // Restricts tessellation to one tile of the grid, see crate::tiled.
+    tile: Option<TileRange>,
+    observer: Option<&'a dyn TessellationObserver>,
+    error_field: Option<&'a dyn ErrorField<S>>,
+    cancellation: Option<CancellationToken>,
+    progress: f32,
+    sampled_volume: f64,
+    sampling_volume: f64,
+}
+
+// Whether a grid point with the given value lies inside the surface. Zero, including negative
+// zero, counts as outside, which perturbs grid points on the surface consistently.
+fn is_inside<S: RealField>(value: S) -> bool {
+    value < S::zero()
+}
+
+// SplitMix64, used to derive reproducible grid offsets for retries.
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+// Returns a value in [0, 1) that only depends on seed, attempt and dim.
+fn retry_jitter(seed: u64, attempt: usize, dim: usize) -> f32 {
+    let bits = splitmix64(splitmix64(seed) ^ (attempt as u64 * 3 + dim as u64));
+    (bits >> 40) as f32 / (1u64 << 24) as f32
+}
+
+// Clamp p into bbox and move coordinates that are within tolerance of a wall onto the wall. Along
+// the rim of a cap, the QEF minimum balances the wall against the curved surface, which would
+// leave the vertices close to, but not on their walls.
+fn snap_to_box<S: RealField + Float>(
+    bbox: &BoundingBox<S>,
+    mut p: na::Vector3<S>,
+    tolerance: S,
+) -> na::Vector3<S> {
+    for i in 0..3 {
+        p[i] = Float::min(Float::max(p[i], bbox.min[i]), bbox.max[i]);
+        if p[i] - bbox.min[i] < tolerance {
+            p[i] = bbox.min[i];
+        } else if bbox.max[i] - p[i] < tolerance {
+            p[i] = bbox.max[i];
+        }
+    }
+    p
+}
+
+// Returns a lower bound of the signed distance to the box along with the normal of the closest
+// wall.
+fn box_value<S: RealField + Float>(
+    bbox: &BoundingBox<S>,
+    p: &na::Point3<S>,
+) -> (S, na::Vector3<S>) {
+    let mut value = S::neg_infinity();
+    let mut normal = na::Vector3::zeros();
+    for i in 0..3 {
+        for (d, sign) in [
+            (bbox.min[i] - p[i], -S::one()),
+            (p[i] - bbox.max[i], S::one()),
+        ] {
+            if d > value {
+                value = d;
+                normal = na::Vector3::zeros();
+                normal[i] = sign;
+            }
+        }
+    }
+    (value, normal)
 }
 
 fn pow2roundup(x: usize) -> usize {
@@ -282,7 +500,7 @@ fn add_child_to_parent<S: RealField + Float + From<f32>>(
     child: &Vertex<S>,
     parent: &mut Vertex<S>,
 ) {
-    parent.qef.borrow_mut().merge(&*child.qef.borrow());
+    parent.qef.merge(&child.qef);
     for dim in 0..3 {
         let relevant_neighbor = dim * 2 + (child.index[dim] & 1);
         for neighbor in &child.neighbors[relevant_neighbor] {
@@ -293,6 +511,119 @@ fn add_child_to_parent<S: RealField + Float + From<f32>>(
     }
 }
 
+// Returns the curves of the surface on the faces of a grid cell, for the patch that crosses the
+// given edges of the cell with the given inside corners.
+fn leaf_face_contours(edges: BitSet, cell: BitSet) -> [FaceContour; 6] {
+    std::array::from_fn(|face| {
+        let crossed: Vec<usize> = edges.intersect(EDGES_ON_FACE[face]).collect();
+        let mut contour = FaceContour::default();
+        match crossed.len() {
+            0 => {}
+            2 => contour.paths.push([crossed[0], crossed[1]]),
+            4 => {
+                // Both diagonals of the face separate the inside corners, which CELL_CONFIGS
+                // cuts off, so each curve connects the edges of one inside corner.
+                for corner in 0..8 {
+                    let corner_edges: Vec<usize> = OUTSIDE_EDGES_PER_CORNER[corner]
+                        .intersect(EDGES_ON_FACE[face])
+                        .collect();
+                    if cell.get(corner) && corner_edges.len() == 2 {
+                        contour.paths.push([corner_edges[0], corner_edges[1]]);
+                    }
+                }
+                contour.tangled = contour.paths.len() != 2;
+            }
+            _ => contour.tangled = true,
+        }
+        contour
+    })
+}
+
+// Traces the curves of the merged surface on the faces of the parent cell by joining the curves
+// of the children on the quarters of each face.
+fn subsample_face_contours<S: RealField>(
+    children: &BTreeSet<usize>,
+    vertices: &[Vertex<S>],
+) -> [FaceContour; 6] {
+    std::array::from_fn(|face| {
+        let (axis, side) = (face / 2, face % 2);
+        let mut contour = FaceContour::default();
+        // Ends of the curves of the children, by twice their position on the grid of child
+        // cells. Ends on the boundary of the face store the edge of the parent they lie on.
+        let mut points = HashMap::new();
+        let mut parent_edges: Vec<Option<usize>> = Vec::new();
+        let mut links: Vec<Vec<usize>> = Vec::new();
+        for vertex in children.iter().map(|&i| &vertices[i]) {
+            let i = vertex.index;
+            if i[axis] & 1 != side {
+                continue;
+            }
+            let child = &vertex.face_contours[face];
+            contour.loops += child.loops;
+            contour.tangled |= child.tangled;
+            let corner = [i[0] & 1, i[1] & 1, i[2] & 1];
+            let outside_edges =
+                OUTSIDE_EDGES_PER_CORNER[corner[2] << 2 | corner[1] << 1 | corner[0]];
+            for path in &child.paths {
+                let ends = path.map(|edge| {
+                    // Several crossings on one edge cannot be told apart.
+                    contour.tangled |= vertex.edge_intersections[edge] > 1;
+                    let mut key = [0; 3];
+                    for d in 0..3 {
+                        key[d] = 2 * (corner[d] + EDGE_OFFSET[edge][d]);
+                    }
+                    key[edge % 3] += 1;
+                    *points.entry(key).or_insert_with(|| {
+                        parent_edges.push(outside_edges.get(edge).then_some(edge));
+                        links.push(Vec::new());
+                        links.len() - 1
+                    })
+                });
+                links[ends[0]].push(ends[1]);
+                links[ends[1]].push(ends[0]);
+            }
+        }
+        if contour.tangled || links.iter().any(|l| l.len() > 2) {
+            contour.tangled = true;
+            return contour;
+        }
+        // Marks the points of the curve through start and returns its other end.
+        let walk = |start: usize, visited: &mut [bool]| {
+            let (mut previous, mut current) = (start, start);
+            loop {
+                visited[current] = true;
+                let next = links[current]
+                    .iter()
+                    .copied()
+                    .find(|&next| next != previous && !visited[next]);
+                match next {
+                    Some(next) => (previous, current) = (current, next),
+                    None => return current,
+                }
+            }
+        };
+        let mut visited = vec![false; links.len()];
+        for start in 0..links.len() {
+            if links[start].len() == 1 && !visited[start] {
+                let end = walk(start, &mut visited);
+                match (parent_edges[start], parent_edges[end]) {
+                    (Some(a), Some(b)) => contour.paths.push([a, b]),
+                    // The curve ends inside the face.
+                    _ => contour.tangled = true,
+                }
+            }
+        }
+        // All remaining points lie on closed curves.
+        for start in 0..links.len() {
+            if !visited[start] {
+                walk(start, &mut visited);
+                contour.loops += 1;
+            }
+        }
+        contour
+    })
+}
+
 fn subsample_euler_characteristics<S: RealField>(
     children: &BTreeSet<usize>,
     vertices: &[Vertex<S>],
@@ -319,17 +650,19 @@ fn subsample_euler_characteristics<S: RealField>(
     (intersections, euler)
 }
 
-fn subsample_octtree<S: RealField + Float + From<f32>>(base: &[Vertex<S>]) -> Vec<Vertex<S>> {
+fn subsample_octtree<S: RealField + Float + From<f32>>(base: &mut [Vertex<S>]) -> Vec<Vertex<S>> {
     let mut result = Vec::new();
-    for (i, vertex) in base.iter().enumerate() {
-        if vertex.parent.get().is_none() {
+    for i in 0..base.len() {
+        if base[i].parent.is_none() {
+            let vertex = &base[i];
             let mut neighbor_set = BTreeSet::new();
             neighbor_set.insert(i);
             add_connected_vertices_in_subcell(base, vertex, &mut neighbor_set);
             let (intersections, euler) = subsample_euler_characteristics(&neighbor_set, base);
+            let face_contours = subsample_face_contours(&neighbor_set, base);
             let mut parent = Vertex {
                 index: half_index(&vertex.index),
-                qef: RefCell::new(qef::Qef::new(&[], BoundingBox::neg_infinity())),
+                qef: qef::Qef::new(&[], BoundingBox::neg_infinity()),
                 neighbors: [
                     Vec::new(),
                     Vec::new(),
@@ -338,25 +671,30 @@ fn subsample_octtree<S: RealField + Float + From<f32>>(base: &[Vertex<S>]) -> Ve
                     Vec::new(),
                     Vec::new(),
                 ],
-                parent: Cell::new(None),
+                parent: None,
                 children: Vec::new(),
-                mesh_index: Cell::new(None),
                 edge_intersections: intersections,
                 euler_characteristic: euler,
+                face_contours,
+                manifold: true,
             };
             for &neighbor_index in &neighbor_set {
-                let child = &base[neighbor_index];
+                let child = &mut base[neighbor_index];
                 debug_assert!(
-                    child.parent.get().is_none(),
+                    child.parent.is_none(),
                     "child #{:?} already has parent #{:?}",
                     neighbor_index,
-                    child.parent.get().unwrap()
+                    child.parent.unwrap()
                 );
                 debug_assert!(!parent.children.contains(&neighbor_index));
                 parent.children.push(neighbor_index);
                 add_child_to_parent(child, &mut parent);
-                child.parent.set(Some(result.len()));
+                child.parent = Some(result.len());
+                parent.manifold &= child.manifold;
             }
+            // A vertex that is not manifold rules out all merges above it, since its children
+            // would otherwise be merged partly.
+            parent.manifold &= parent.is_2manifold();
             result.push(parent);
         }
     }
@@ -365,9 +703,7 @@ fn subsample_octtree<S: RealField + Float + From<f32>>(base: &[Vertex<S>]) -> Ve
             for neighbor in neighbor_vec.iter_mut() {
                 match *neighbor {
                     VarIndex::VertexIndex(_) => panic!("unexpected VertexIndex in normal node."),
-                    VarIndex::Index(i) => {
-                        *neighbor = VarIndex::Index(base[i].parent.get().unwrap())
-                    }
+                    VarIndex::Index(i) => *neighbor = VarIndex::Index(base[i].parent.unwrap()),
                 }
             }
         }
@@ -395,6 +731,20 @@ impl Timer {
     }
 }
 
+// std::time::Instant panics on wasm32-unknown-unknown.
+#[cfg(target_arch = "wasm32")]
+struct Timer;
+
+#[cfg(target_arch = "wasm32")]
+impl Timer {
+    fn new() -> Timer {
+        Timer
+    }
+    fn elapsed(&mut self) -> std::time::Duration {
+        std::time::Duration::ZERO
+    }
+}
+
 impl<'a, S: From<f32> + RealField + Float + AsUSize> ManifoldDualContouring<'a, S> {
     /// Constructor
     /// f: function to tessellate
@@ -405,203 +755,1040 @@ impl<'a, S: From<f32> + RealField + Float + AsUSize> ManifoldDualContouring<'a,
         res: S,
         relative_error: S,
     ) -> ManifoldDualContouring<'a, S> {
-        let one: S = From::from(1f32);
-        let bbox = f.bbox().dilate(one + res * From::from(1.1f32));
+        ManifoldDualContouring::with_config(f, res, relative_error, TessellationConfig::default())
+    }
+    /// Constructor with explicit tuning parameters, see [`TessellationConfig`].
+    pub fn with_config(
+        f: &'a dyn ImplicitFunction<S>,
+        res: S,
+        relative_error: S,
+        config: TessellationConfig<S>,
+    ) -> ManifoldDualContouring<'a, S> {
+        let padding = config.bbox_padding + res * config.bbox_padding_cells;
+        let (bbox, cap, clip) = match (&config.region, config.boundary) {
+            (Some(region), Boundary::Open) => (region.clone(), None, true),
+            (Some(region), Boundary::Capped) => {
+                (region.dilate(padding), Some(region.clone()), false)
+            }
+            (None, Boundary::Open) => (f.bbox().dilate(padding), None, false),
+            (None, Boundary::Capped) => (f.bbox().dilate(padding), Some(f.bbox().clone()), false),
+        };
+        let (lipschitz, cap_scale) = match cap {
+            // Both the scaled function and the distance to the capping box change at most as
+            // fast as the distance.
+            Some(_) if f.lipschitz() > S::zero() && Float::is_finite(f.lipschitz()) => {
+                (S::one(), S::one() / f.lipschitz())
+            }
+            Some(_) => (Float::max(f.lipschitz(), S::one()), S::one()),
+            None => (f.lipschitz(), S::one()),
+        };
         ManifoldDualContouring {
             function: f,
             origin: bbox.min,
+            base_origin: bbox.min,
             dim: [
                 Float::ceil(bbox.dim()[0] / res).as_usize(),
                 Float::ceil(bbox.dim()[1] / res).as_usize(),
                 Float::ceil(bbox.dim()[2] / res).as_usize(),
             ],
-            mesh: RefCell::new(Mesh {
-                vertices: Vec::new(),
-                faces: Vec::new(),
-            }),
             res,
             error: res * relative_error,
             value_grid: HashMap::new(),
-            edge_grid: RefCell::new(HashMap::new()),
+            edge_grid: HashMap::new(),
             vertex_octtree: Vec::new(),
             vertex_index_map: HashMap::new(),
+            config,
+            cap,
+            cap_scale,
+            clip,
+            lipschitz,
+            tile: None,
+            observer: None,
+            error_field: None,
+            cancellation: None,
+            progress: 0.,
+            sampled_volume: 0.,
+            sampling_volume: 0.,
         }
     }
+    /// Set an observer that gets notified about the progress of tessellate.
+    pub fn set_observer(&mut self, observer: &'a dyn TessellationObserver) {
+        self.observer = Some(observer);
+    }
+    /// Set a field that replaces the constant `relative_error` passed to the constructor, so that
+    /// some regions keep more detail than others. The field is evaluated at the center of each
+    /// cell that simplification considers merging.
+    pub fn set_error_field(&mut self, field: &'a dyn ErrorField<S>) {
+        self.error_field = Some(field);
+    }
+    /// Set a token through which tessellate can be aborted. It is checked between stages and
+    /// regularly during sampling and face generation.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
     /// Tessellate the given function.
-    pub fn tessellate(&mut self) -> Option<Mesh<S>> {
-        #[cfg(not(target_arch = "wasm32"))]
-        println!(
-            "ManifoldDualContouring: res: {:} {:?}",
-            self.res,
-            self.function.bbox()
-        );
-        loop {
-            match self.try_tessellate() {
-                Ok(mesh) => return Some(mesh),
-                Err(_e) => {
-                    let padding = na::Vector3::new(
-                        -self.res / From::from(10. + rand::random::<f32>().abs()),
-                        -self.res / From::from(10. + rand::random::<f32>().abs()),
-                        -self.res / From::from(10. + rand::random::<f32>().abs()),
-                    );
-                    #[cfg(not(target_arch = "wasm32"))]
-                    println!("Error: {:?}. moving by {:?} and retrying.", _e, padding);
-                    self.origin += padding;
-                    self.value_grid.clear();
-                    self.mesh.borrow_mut().vertices.clear();
-                    self.mesh.borrow_mut().faces.clear();
-                    self.vertex_octtree.clear();
-                    self.vertex_index_map.clear();
+    pub fn tessellate(&mut self) -> Result<Mesh<S>, DualContouringError> {
+        self.tessellate_with_stats().map(|(mesh, _)| mesh)
+    }
+    /// Tessellate the given function and return statistics about the run along with the mesh.
+    pub fn tessellate_with_stats(
+        &mut self,
+    ) -> Result<(Mesh<S>, TessellationStats), DualContouringError> {
+        let mut mesh = Mesh::default();
+        let mut stats = self.tessellate_into(&mut mesh)?;
+        if self.clip {
+            stats.boundary_loops = mesh.boundary_loops().len();
+        }
+        Ok((mesh, stats))
+    }
+    /// Tessellate the given function and pass the vertices and faces to `sink` as they are
+    /// generated, instead of collecting them in a [`Mesh`].
+    ///
+    /// Nothing is passed to the sink before the surface was sampled successfully, so retries do
+    /// not produce partial output. If tessellation is cancelled while faces are generated, the
+    /// sink has received a part of the mesh and [`MeshSink::finish`] is not called.
+    pub fn tessellate_into(
+        &mut self,
+        sink: &mut dyn MeshSink<S>,
+    ) -> Result<TessellationStats, DualContouringError> {
+        let mut stats = TessellationStats::default();
+        let (vertices, faces) =
+            self.with_retries(&mut stats, |mdc, stats| mdc.try_tessellate(stats, sink))?;
+        stats.vertices = vertices;
+        stats.faces = faces;
+        Ok(stats)
+    }
+    /// Run all stages up to solving the QEFs and keep the vertex octree, from which meshes for
+    /// several error thresholds or octree depths can be extracted without sampling the function
+    /// again, e.g. for chains of progressive levels of detail.
+    ///
+    /// Unlike [`tessellate`](Self::tessellate), this solves the QEFs of all octree levels. The
+    /// `relative_error` passed to the constructor and the error field are not used.
+    pub fn build_octree(&mut self) -> Result<VertexOctree<'_, 'a, S>, DualContouringError> {
+        let mut stats = TessellationStats::default();
+        self.with_retries(&mut stats, |mdc, stats| {
+            let mut t = Timer::new();
+            stats.stages.clear();
+            mdc.progress = 0.;
+            mdc.clear();
+            // Every parent error exceeds this, so all layers are solved.
+            let solve_all = Collapse {
+                error: Float::neg_infinity(),
+                field: None,
+                max_layer: usize::MAX,
+                rejected: None,
+            };
+            mdc.build_vertex_octtree(stats, &mut t, &solve_all)
+        })?;
+        Ok(VertexOctree { mdc: self, stats })
+    }
+
+    // Calls attempt until it does not hit an exact zero, shifting the grid in between.
+    fn with_retries<T>(
+        &mut self,
+        stats: &mut TessellationStats,
+        mut attempt: impl FnMut(&mut Self, &mut TessellationStats) -> Result<T, DualContouringError>,
+    ) -> Result<T, DualContouringError> {
+        self.check_grid()?;
+        for i in 0..=self.config.max_retries {
+            self.set_attempt(i);
+            match attempt(self, stats) {
+                Err(e @ DualContouringError::HitZero(_)) => {
+                    if let Some(observer) = self.observer {
+                        observer.retry(&e);
+                    }
+                    stats.retries += 1;
                 }
+                result => return result,
             }
         }
+        Err(DualContouringError::RetriesExhausted(
+            self.config.max_retries + 1,
+        ))
     }
 
-    fn tessellation_step1(&mut self) -> Option<DualContouringError> {
-        let maxdim = cmp::max(self.dim[0], cmp::max(self.dim[1], self.dim[2]));
-        let origin = self.origin;
-        let origin_value = self.function.value(&origin);
-
-        self.sample_value_grid([0, 0, 0], origin, pow2roundup(maxdim), origin_value)
+    // Number of octree layers that lookup_vertex can return nodes from.
+    pub(crate) fn octree_depth(&self) -> usize {
+        cmp::max(self.vertex_octtree.len(), 2) - 1
     }
 
-    fn try_tessellate(&mut self) -> Result<Mesh<S>, DualContouringError> {
-        #[cfg(not(target_arch = "wasm32"))]
-        let mut t = Timer::new();
+    // Passes the mesh for the given level of detail of the octree built by build_octree to the
+    // sink. Returns the number of vertices and faces.
+    pub(crate) fn extract_into(
+        &self,
+        lod: LevelOfDetail<S>,
+        sink: &mut dyn MeshSink<S>,
+    ) -> Result<(usize, usize), DualContouringError> {
+        let edges = self.sorted_edges();
+        let with_rejections = |collapse: Collapse<'a, S>| {
+            let rejected = self.topology_rejections(&edges, &collapse)?;
+            Ok((collapse, rejected))
+        };
+        let (collapse, rejected) = match lod {
+            LevelOfDetail::RelativeError(relative_error) => with_rejections(Collapse {
+                error: self.res * relative_error,
+                field: None,
+                max_layer: usize::MAX,
+                rejected: None,
+            }),
+            LevelOfDetail::Depth(depth) => with_rejections(Collapse {
+                error: Float::infinity(),
+                field: None,
+                max_layer: depth,
+                rejected: None,
+            }),
+            LevelOfDetail::FaceBudget(faces) => self.collapse_for_budget(&edges, faces),
+        }?;
+        self.report_rejections(rejected.as_deref());
+        let collapse = Collapse {
+            rejected: rejected.as_deref(),
+            ..collapse
+        };
+        let mut output = MeshOutput::new(sink);
+        let mut numbering = NodeNumbering::new(&self.vertex_octtree);
+        for chunk in edges.chunks(QUAD_CHUNK_SIZE) {
+            self.check_cancelled()?;
+            self.output_faces(chunk, &collapse, &mut numbering, &mut output)?;
+        }
+        output.sink.finish().map_err(DualContouringError::Io)?;
+        Ok((output.vertices, output.faces))
+    }
 
-        if let Some(e) = self.tessellation_step1() {
-            return Err(e);
+    // Searches the error threshold whose mesh has the face count closest to the budget. The
+    // mesh only changes where the threshold passes the error of a node, so the candidates are
+    // the errors of all nodes that lookup_vertex may return. Coarser thresholds give fewer faces,
+    // apart from a few exceptions due to manifold checks, so a binary search finds the first
+    // candidate within the budget.
+    //
+    // Rejecting merges to preserve the topology is too expensive for every probe, so it is only
+    // applied to the result. Rejected merges keep parts of the mesh finer, so if the budget is
+    // exceeded then, coarser candidates are probed in growing steps and the last step searched
+    // again. Returns the threshold along with the merges that are rejected for it.
+    fn collapse_for_budget(
+        &self,
+        edges: &[EdgeIndex],
+        faces: usize,
+    ) -> Result<(Collapse<'a, S>, Option<RejectedNodes>), DualContouringError> {
+        let layers = self.vertex_octtree.len().saturating_sub(1);
+        let mut errors: Vec<S> = std::iter::once(S::zero())
+            .chain(
+                self.vertex_octtree
+                    .iter()
+                    .take(layers)
+                    .skip(1)
+                    .flatten()
+                    .map(|vertex| vertex.qef.error)
+                    .filter(|error| !error.is_nan()),
+            )
+            .collect();
+        errors.sort_unstable_by(|a, b| a.partial_cmp(b).unwrap());
+        errors.dedup();
+        let last = errors.len() - 1;
+        let collapse = |error| Collapse {
+            error,
+            field: None,
+            max_layer: usize::MAX,
+            rejected: None,
+        };
+        let plain = |index: usize| {
+            Ok(BudgetProbe {
+                index,
+                faces: self.count_faces(edges, &collapse(errors[index])),
+                rejected: None,
+            })
+        };
+        let finest = plain(0)?;
+        let mut best = if finest.faces <= faces {
+            finest
+        } else {
+            let coarsest = plain(last)?;
+            if coarsest.faces >= faces {
+                coarsest
+            } else {
+                self.search_budget(faces, finest, coarsest, plain)?
+            }
+        };
+        if self.config.preserve_topology {
+            let with_rejections = |index: usize| {
+                let rejected = self.reject_topology_changes(edges, &collapse(errors[index]))?;
+                let collapse = Collapse {
+                    rejected: Some(&rejected),
+                    ..collapse(errors[index])
+                };
+                Ok(BudgetProbe {
+                    index,
+                    faces: self.count_faces(edges, &collapse),
+                    rejected: Some(rejected),
+                })
+            };
+            best = with_rejections(best.index)?;
+            let mut step = 1;
+            while best.faces > faces && best.index < last {
+                self.check_cancelled()?;
+                let next = with_rejections((best.index + step).min(last))?;
+                if next.faces > faces {
+                    best = next;
+                    step *= 2;
+                } else {
+                    best = self.search_budget(faces, best, next, with_rejections)?;
+                    break;
+                }
+            }
         }
+        Ok((collapse(errors[best.index]), best.rejected))
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let total_cells = self.dim[0] * self.dim[1] * self.dim[2];
-            println!(
-                "generated value_grid with {:} % of {:} cells in {:?}.",
-                (100 * self.value_grid.len()) as f64 / total_cells as f64,
-                total_cells,
-                t.elapsed()
-            );
+    // Binary search between two candidates, the first above and the second within the budget,
+    // for the candidate whose face count is closest to it.
+    fn search_budget(
+        &self,
+        faces: usize,
+        mut lower: BudgetProbe,
+        mut upper: BudgetProbe,
+        count: impl Fn(usize) -> Result<BudgetProbe, DualContouringError>,
+    ) -> Result<BudgetProbe, DualContouringError> {
+        while upper.index - lower.index > 1 {
+            self.check_cancelled()?;
+            let middle = count((lower.index + upper.index) / 2)?;
+            if middle.faces > faces {
+                lower = middle;
+            } else {
+                upper = middle;
+            }
         }
+        Ok(if lower.faces - faces < faces - upper.faces {
+            lower
+        } else {
+            upper
+        })
+    }
 
-        self.compact_value_grid();
+    // Number of triangles of the mesh for the given threshold.
+    fn count_faces(&self, edges: &[EdgeIndex], collapse: &Collapse<S>) -> usize {
+        edges
+            .chunks(QUAD_CHUNK_SIZE)
+            .map(|chunk| {
+                self.polygons(chunk, collapse)
+                    .iter()
+                    .map(|polygon| polygon.len() - 2)
+                    .sum::<usize>()
+            })
+            .sum()
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        {
-            let total_cells = self.dim[0] * self.dim[1] * self.dim[2];
-            println!(
-                "compacted value_grid, now {:} % of {:} cells in {:?}.",
-                (100 * self.value_grid.len()) as f64 / total_cells as f64,
-                total_cells,
-                t.elapsed()
-            );
+    // Merges cells as far as the error threshold passed to the constructor or the error field
+    // allows.
+    fn collapse(&self) -> Collapse<'a, S> {
+        Collapse {
+            error: self.error,
+            field: self.error_field,
+            max_layer: usize::MAX,
+            rejected: None,
         }
+    }
 
-        self.generate_edge_grid();
-
-        #[cfg(not(target_arch = "wasm32"))]
-        println!(
-            "generated edge_grid with {} edges: {:?}",
-            self.edge_grid.borrow().len(),
-            t.elapsed()
-        );
+    // Rejects merges until the mesh is manifold and its components have the Euler
+    // characteristics and boundary loops of the mesh of the leaf cells. Returns the rejected
+    // nodes per layer.
+    //
+    // Every round rejects the merged defects of the mesh along with the merged nodes around
+    // defects that are leafs, or if the topology changed without a local defect, the coarsest
+    // layer in use. Rejected
+    // nodes are not used by the next mesh, so this ends with the leaf mesh at the latest.
+    fn reject_topology_changes(
+        &self,
+        edges: &[EdgeIndex],
+        collapse: &Collapse<S>,
+    ) -> Result<RejectedNodes, DualContouringError> {
+        let mut rejected: RejectedNodes = self
+            .vertex_octtree
+            .iter()
+            .map(|layer| vec![false; layer.len()])
+            .collect();
+        let leafs = Collapse {
+            max_layer: 0,
+            ..*collapse
+        };
+        let reference = Topology::new(&self.triangles(edges, &leafs)).components;
+        loop {
+            self.check_cancelled()?;
+            let current = Collapse {
+                rejected: Some(&rejected),
+                ..*collapse
+            };
+            let triangles = self.triangles(edges, &current);
+            let topology = Topology::new(&triangles);
+            if topology.defects.is_empty() && topology.components == reference {
+                break;
+            }
+            let merged = |&(layer, _): &(usize, usize)| layer > 0;
+            let unmerged_defect =
+                |node: &(usize, usize)| topology.defects.contains(node) && !merged(node);
+            let mut nodes: BTreeSet<(usize, usize)> = topology
+                .defects
+                .iter()
+                .copied()
+                .filter(merged)
+                .chain(
+                    triangles
+                        .iter()
+                        .filter(|t| t.iter().any(unmerged_defect))
+                        .flatten()
+                        .copied()
+                        .filter(merged),
+                )
+                .collect();
+            if nodes.is_empty() {
+                // Merged nodes may not appear in any face, e.g. if a component vanished.
+                let used: BTreeSet<(usize, usize)> = self
+                    .vertex_index_map
+                    .keys()
+                    .filter_map(|&vertex_index| self.lookup_vertex(vertex_index, &current))
+                    .collect();
+                let top = used.iter().map(|&(layer, _)| layer).max();
+                nodes = used
+                    .into_iter()
+                    .filter(|node| merged(node) && Some(node.0) == top)
+                    .collect();
+            }
+            if nodes.is_empty() {
+                break;
+            }
+            for (layer, index) in nodes {
+                rejected[layer][index] = true;
+            }
+        }
+        Ok(rejected)
+    }
 
-        let (leafs, index_map) = self.generate_leaf_vertices();
-        self.vertex_index_map = index_map;
-        self.vertex_octtree.push(leafs);
+    // The merges that reject_topology_changes rejects, if the config asks to preserve topology.
+    fn topology_rejections(
+        &self,
+        edges: &[EdgeIndex],
+        collapse: &Collapse<S>,
+    ) -> Result<Option<RejectedNodes>, DualContouringError> {
+        self.config
+            .preserve_topology
+            .then(|| self.reject_topology_changes(edges, collapse))
+            .transpose()
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        println!(
-            "generated {:?} leaf vertices: {:?}",
-            self.vertex_octtree[0].len(),
-            t.elapsed()
-        );
+    // Passes the rejected merges to the observer and returns their number.
+    fn report_rejections(&self, rejected: Option<&[Vec<bool>]>) -> usize {
+        let mut count = 0;
+        for (layer, nodes) in rejected.into_iter().flatten().enumerate() {
+            for (index, _) in nodes.iter().enumerate().filter(|(_, &rejected)| rejected) {
+                if let Some(observer) = self.observer {
+                    observer.collapse_rejected(layer, self.vertex_octtree[layer][index].index);
+                }
+                count += 1;
+            }
+        }
+        count
+    }
 
-        loop {
-            let next = subsample_octtree(self.vertex_octtree.last().unwrap());
-            if next.len() == self.vertex_octtree.last().unwrap().len() {
-                break;
+    // Returns the triangles of the mesh as the layers and indexes of their corners.
+    fn triangles(&self, edges: &[EdgeIndex], collapse: &Collapse<S>) -> Vec<[(usize, usize); 3]> {
+        let mut triangles = Vec::new();
+        for polygon in self.polygons(edges, collapse) {
+            let p: Vec<(usize, usize)> = polygon
+                .into_iter()
+                .map(|corner| match corner {
+                    Corner::Node(layer, index) => (layer, index),
+                    Corner::Neighbor(vi) => panic!("unexpected neighbor {:?}", vi),
+                })
+                .collect();
+            triangles.push([p[0], p[1], p[2]]);
+            if p.len() == 4 {
+                triangles.push([p[2], p[3], p[0]]);
             }
-            self.vertex_octtree.push(next);
         }
+        triangles
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        println!("subsampled octtree {:?}", t.elapsed());
+    // The error up to which the given node of the octree layer may replace its children.
+    fn max_error(&self, collapse: &Collapse<S>, layer: usize, vertex: &Vertex<S>) -> S {
+        let Some(field) = collapse.field else {
+            return collapse.error;
+        };
+        let size = self.res * Float::powi(From::from(2f32), layer as i32);
+        let half: S = From::from(0.5f32);
+        let center = self.origin
+            + na::Vector3::new(
+                From::from(vertex.index[0] as f32),
+                From::from(vertex.index[1] as f32),
+                From::from(vertex.index[2] as f32),
+            )
+            .add_scalar(half)
+                * size;
+        self.res * field.relative_error(&center)
+    }
+
+    // Places the grid for the given attempt. Each retry moves it by another fraction of a cell,
+    // so that it does not hit the same zero. The position only depends on seed and attempt, not
+    // on earlier calls.
+    pub(crate) fn set_attempt(&mut self, attempt: usize) {
+        self.origin = self.base_origin;
+        for a in 0..attempt {
+            let padding = na::Vector3::new(
+                -self.res / From::from(10. + retry_jitter(self.config.seed, a, 0)),
+                -self.res / From::from(10. + retry_jitter(self.config.seed, a, 1)),
+                -self.res / From::from(10. + retry_jitter(self.config.seed, a, 2)),
+            );
+            self.origin += padding;
+        }
+    }
 
-        let _num_qefs_solved = self.solve_qefs();
+    // Number of cells of the grid along each axis.
+    pub(crate) fn dim(&self) -> [usize; 3] {
+        self.dim
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        println!("solved {} qefs: {:?}", _num_qefs_solved, t.elapsed());
+    fn position(&self, idx: Index) -> na::Point3<S> {
+        self.origin
+            + na::Vector3::new(
+                From::from(idx[0] as f32),
+                From::from(idx[1] as f32),
+                From::from(idx[2] as f32),
+            ) * self.res
+    }
 
-        for edge_index in self.edge_grid.borrow().keys() {
-            self.compute_quad(*edge_index);
+    fn finish_stage(
+        &mut self,
+        stats: &mut TessellationStats,
+        timer: &mut Timer,
+        stage: Stage,
+        count: usize,
+    ) -> Result<(), DualContouringError> {
+        let event = StageEvent {
+            stage,
+            count,
+            duration: timer.elapsed(),
+        };
+        if let Some(observer) = self.observer {
+            observer.stage_finished(&event);
         }
+        stats.stages.push(event);
+        self.report_progress(stage, 1.);
+        self.check_cancelled()
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        println!("generated quads: {:?}", t.elapsed());
+    fn check_cancelled(&self) -> Result<(), DualContouringError> {
+        match self.cancellation {
+            Some(ref token) if token.is_cancelled() => Err(DualContouringError::Cancelled),
+            _ => Ok(()),
+        }
+    }
 
-        #[cfg(not(target_arch = "wasm32"))]
-        println!(
-            "computed mesh with {:?} faces.",
-            self.mesh.borrow().faces.len()
-        );
+    // Forwards the overall progress to the observer, at most once per percent.
+    fn report_progress(&mut self, stage: Stage, fraction_of_stage: f32) {
+        let Some(observer) = self.observer else {
+            return;
+        };
+        let stage = stage as usize;
+        let progress =
+            STAGE_WEIGHTS[..stage].iter().sum::<f32>() + STAGE_WEIGHTS[stage] * fraction_of_stage;
+        let progress = progress.min(1.);
+        if progress >= self.progress + 0.01 || (progress == 1. && self.progress < 1.) {
+            self.progress = progress;
+            observer.progress(progress);
+        }
+    }
 
-        Ok(self.mesh.borrow().clone())
+    // Evaluates the function, intersected with the capping box if there is one.
+    fn value(&self, p: &na::Point3<S>) -> S {
+        let value = self.function.value(p);
+        match self.cap {
+            Some(ref cap) => Float::max(value * self.cap_scale, box_value(cap, p).0),
+            None => value,
+        }
     }
 
-    fn sample_value_grid(
-        &mut self,
-        idx: Index,
-        pos: na::Point3<S>,
-        size: usize,
-        val: S,
-    ) -> Option<DualContouringError> {
-        debug_assert!(size > 1);
-        let mut midx = idx;
-        let size = size / 2;
-        let size_s: S = From::from(size as f32);
-        let vpos = [
-            pos,
-            pos + na::Vector3::new(self.res, self.res, self.res) * size_s,
-        ];
-        let sub_cube_diagonal = size_s * self.res * Float::sqrt(From::from(3f32));
-
-        for z in 0..2 {
-            for y in 0..2 {
-                for x in 0..2 {
-                    let mpos = na::Point3::new(vpos[x].x, vpos[y].y, vpos[z].z);
-                    let value = if midx == idx {
-                        val
-                    } else {
-                        self.function.value(&mpos)
-                    };
-
-                    if value == From::from(0f32) {
-                        return Some(DualContouringError::HitZero(format!("{}", mpos)));
-                    }
+    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+        if let Some(ref cap) = self.cap {
+            let (value, normal) = box_value(cap, p);
+            if value >= self.function.value(p) * self.cap_scale {
+                return normal;
+            }
+        }
+        self.function.normal(p, self.normal_epsilon())
+    }
 
-                    if size > 1 && Float::abs(value) <= sub_cube_diagonal {
-                        if let Some(e) = self.sample_value_grid(midx, mpos, size, value) {
-                            return Some(e);
-                        }
-                    } else {
-                        self.value_grid.insert(midx, value);
+    fn value_and_gradient(&self, p: &na::Point3<S>) -> (S, na::Vector3<S>) {
+        let (value, gradient) = self.function.value_and_gradient(p, self.normal_epsilon());
+        match self.cap {
+            Some(ref cap) => {
+                let (box_value, box_normal) = box_value(cap, p);
+                if box_value >= value * self.cap_scale {
+                    (box_value, box_normal)
+                } else {
+                    (value * self.cap_scale, gradient * self.cap_scale)
+                }
+            }
+            None => (value, gradient),
+        }
+    }
+
+    // Offset of numerically approximated normals, scaled to the resolution.
+    fn normal_epsilon(&self) -> S {
+        self.res * From::from(NORMAL_EPSILON)
+    }
+
+    // Evaluates the function on a batch of points, intersected with the capping box.
+    fn value_batch(&self, points: &[na::Point3<S>], values: &mut [S]) {
+        self.function.value_batch(points, values);
+        if let Some(ref cap) = self.cap {
+            for (p, v) in points.iter().zip(values.iter_mut()) {
+                *v = Float::max(*v * self.cap_scale, box_value(cap, p).0);
+            }
+        }
+    }
+
+    // Bounds the function within bbox, intersected with the capping box.
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let (lower, upper) = self.function.value_interval(bbox);
+        match self.cap {
+            Some(ref cap) => {
+                // An unbounded interval stays unbounded.
+                let (lower, upper) = if is_unbounded(&lower, &upper) {
+                    (lower, upper)
+                } else {
+                    (lower * self.cap_scale, upper * self.cap_scale)
+                };
+                // The distance to the box is the maximum over its walls, each of which is linear.
+                let mut box_lower = S::neg_infinity();
+                let mut box_upper = S::neg_infinity();
+                for i in 0..3 {
+                    for (l, u) in [
+                        (cap.min[i] - bbox.max[i], cap.min[i] - bbox.min[i]),
+                        (bbox.min[i] - cap.max[i], bbox.max[i] - cap.max[i]),
+                    ] {
+                        box_lower = Float::max(box_lower, l);
+                        box_upper = Float::max(box_upper, u);
                     }
-                    midx[0] += size;
                 }
-                midx[0] -= 2 * size;
-                midx[1] += size;
+                (Float::max(lower, box_lower), Float::max(upper, box_upper))
             }
-            midx[1] -= 2 * size;
-            midx[2] += size;
+            None => (lower, upper),
         }
-        None
     }
 
-    fn compact_value_grid(&mut self) {
-        let value_grid = &mut self.value_grid;
-        #[cfg(not(target_arch = "wasm32"))]
-        let iter = value_grid.par_iter();
+    // Returns true if the cube of the given size at idx, whose first corner has the given value,
+    // may contain the surface and needs to be subdivided.
+    fn may_contain_surface(&self, idx: Index, size: usize, value: S) -> bool {
+        let zero = S::zero();
+        let upper_idx = [idx[0] + size, idx[1] + size, idx[2] + size];
+        // Grid points on the lower walls of the cube end edges that reach into the neighboring
+        // cubes, so the cube is extended by one cell.
+        let bbox =
+            BoundingBox::new(&self.position(idx), &self.position(upper_idx)).dilate(self.res);
+        let (lower, upper) = self.value_interval(&bbox);
+        if lower > zero || upper < zero {
+            return false;
+        }
+        if !is_unbounded(&lower, &upper) {
+            return true;
+        }
+        // Without a bound, the value changes at most by the Lipschitz constant times the distance.
+        let size_s: S = From::from(size as f32);
+        Float::abs(value) <= size_s * self.res * Float::sqrt(From::from(3f32)) * self.lipschitz
+    }
+
+    fn normal_batch(&self, points: &[na::Point3<S>]) -> Vec<na::Vector3<S>> {
+        let mut normals = vec![na::Vector3::zeros(); points.len()];
+        self.function
+            .normal_batch(points, &mut normals, self.normal_epsilon());
+        if let Some(ref cap) = self.cap {
+            let mut values = vec![S::zero(); points.len()];
+            self.function.value_batch(points, &mut values);
+            for ((p, v), n) in points.iter().zip(values).zip(normals.iter_mut()) {
+                let (box_value, box_normal) = box_value(cap, p);
+                if box_value >= v * self.cap_scale {
+                    *n = box_normal;
+                }
+            }
+        }
+        normals
+    }
+
+    // Returns true if idx is a cell within the grid, or if the grid is not clipped.
+    fn cell_in_grid(&self, idx: Index) -> bool {
+        !self.clip || (0..3).all(|i| idx[i] < self.dim[i])
+    }
+
+    // Returns true if the cell belongs to the tile that is tessellated, or if there is no tile.
+    fn cell_in_tile(&self, idx: Index) -> bool {
+        self.tile.as_ref().map_or(true, |tile| {
+            (0..3).all(|i| tile.cells[0][i] <= idx[i] && idx[i] < tile.cells[1][i])
+        })
+    }
+
+    // Returns true if the grid point is a corner of a cell of the tile, or if there is no tile.
+    fn point_in_tile(&self, idx: Index) -> bool {
+        self.tile.as_ref().map_or(true, |tile| {
+            (0..3).all(|i| tile.cells[0][i] <= idx[i] && idx[i] <= tile.cells[1][i])
+        })
+    }
+
+    fn check_grid(&self) -> Result<(), DualContouringError> {
+        let bbox = self
+            .config
+            .region
+            .as_ref()
+            .unwrap_or_else(|| self.function.bbox());
+        let finite = |p: &na::Point3<S>| p.iter().all(|c| Float::is_finite(*c));
+        if !finite(&bbox.min) || !finite(&bbox.max) {
+            return Err(DualContouringError::DegenerateBoundingBox(format!(
+                "non-finite bbox {:?}",
+                bbox
+            )));
+        }
+        if (0..3).any(|i| bbox.min[i] > bbox.max[i]) {
+            return Err(DualContouringError::DegenerateBoundingBox(format!(
+                "inverted bbox {:?}",
+                bbox
+            )));
+        }
+        if !Float::is_finite(self.res) || self.res <= From::from(0f32) {
+            return Err(DualContouringError::DegenerateBoundingBox(format!(
+                "invalid resolution {:?}",
+                self.res
+            )));
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self) {
+        self.value_grid.clear();
+        self.edge_grid.clear();
+        self.vertex_octtree.clear();
+        self.vertex_index_map.clear();
+    }
+
+    fn tessellation_step1(&mut self) -> Result<(), DualContouringError> {
+        let maxdim = cmp::max(self.dim[0], cmp::max(self.dim[1], self.dim[2]));
+        let origin_value = self.value(&self.origin);
+        let size = pow2roundup(maxdim);
+        self.sampled_volume = 0.;
+        self.sampling_volume = (size as f64).powi(3);
+
+        self.sample_value_grid([0, 0, 0], size, origin_value)
+    }
+
+    // Returns the number of vertices and faces passed to the sink.
+    fn try_tessellate(
+        &mut self,
+        stats: &mut TessellationStats,
+        sink: &mut dyn MeshSink<S>,
+    ) -> Result<(usize, usize), DualContouringError> {
+        let mut t = Timer::new();
+        stats.stages.clear();
+        self.progress = 0.;
+
+        self.clear();
+        let collapse = self.collapse();
+        self.build_vertex_octtree(stats, &mut t, &collapse)?;
+
+        let edges = self.sorted_edges();
+        let rejected = self.topology_rejections(&edges, &collapse)?;
+        stats.rejected_collapses = self.report_rejections(rejected.as_deref());
+        let collapse = Collapse {
+            rejected: rejected.as_deref(),
+            ..collapse
+        };
+        let mut output = MeshOutput::new(sink);
+        let mut numbering = NodeNumbering::new(&self.vertex_octtree);
+        for (i, chunk) in edges.chunks(QUAD_CHUNK_SIZE).enumerate() {
+            self.check_cancelled()?;
+            self.output_faces(chunk, &collapse, &mut numbering, &mut output)?;
+            let done = cmp::min((i + 1) * QUAD_CHUNK_SIZE, edges.len());
+            self.report_progress(Stage::GenerateQuads, done as f32 / edges.len() as f32);
+        }
+        let count = output.faces;
+        self.finish_stage(stats, &mut t, Stage::GenerateQuads, count)?;
+        output.sink.finish().map_err(DualContouringError::Io)?;
+
+        Ok((output.vertices, output.faces))
+    }
+
+    // Passes the faces around the given edges to the output, preceded by the vertices that they
+    // use for the first time.
+    fn output_faces(
+        &self,
+        edges: &[EdgeIndex],
+        collapse: &Collapse<S>,
+        numbering: &mut NodeNumbering,
+        output: &mut MeshOutput<S>,
+    ) -> Result<(), DualContouringError> {
+        let first_new_node = numbering.nodes.len();
+        let faces: Vec<Vec<usize>> = self
+            .polygons(edges, collapse)
+            .into_iter()
+            .map(|polygon| {
+                polygon
+                    .into_iter()
+                    .map(|corner| match corner {
+                        Corner::Node(layer, index) => numbering.number(layer, index),
+                        Corner::Neighbor(vi) => panic!("unexpected neighbor {:?}", vi),
+                    })
+                    .collect()
+            })
+            .collect();
+        for position in self.node_positions(&numbering.nodes[first_new_node..]) {
+            output.add_vertex(position)?;
+        }
+        for p in faces {
+            output.add_face([p[0], p[1], p[2]])?;
+            if p.len() == 4 {
+                output.add_face([p[2], p[3], p[0]])?;
+            }
+        }
+        Ok(())
+    }
+
+    // Runs all stages up to and including solving the QEFs. Children are only solved if their
+    // parent may not replace them under collapse.
+    fn build_vertex_octtree(
+        &mut self,
+        stats: &mut TessellationStats,
+        t: &mut Timer,
+        collapse: &Collapse<S>,
+    ) -> Result<(), DualContouringError> {
+        self.check_cancelled()?;
+        self.tessellation_step1()?;
+        let count = self.value_grid.len();
+        self.finish_stage(stats, t, Stage::SampleValueGrid, count)?;
+
+        self.compact_value_grid();
+        let count = self.value_grid.len();
+        self.finish_stage(stats, t, Stage::CompactValueGrid, count)?;
+
+        self.generate_edge_grid();
+        let count = self.edge_grid.len();
+        self.finish_stage(stats, t, Stage::EdgeGrid, count)?;
+
+        if self.edge_grid.is_empty() && self.tile.is_none() {
+            return Err(DualContouringError::EmptySurface);
+        }
+
+        let (leafs, index_map) = self.generate_leaf_vertices();
+        self.vertex_index_map = index_map;
+        self.vertex_octtree.push(leafs);
+        let count = self.vertex_octtree[0].len();
+        self.finish_stage(stats, t, Stage::LeafVertices, count)?;
+
+        // The topmost layer is never used for the mesh, so a tile keeps one more than it uses.
+        while self.config.simplify
+            && self
+                .tile
+                .as_ref()
+                .map_or(true, |tile| self.vertex_octtree.len() < tile.levels + 2)
+        {
+            let next = subsample_octtree(self.vertex_octtree.last_mut().unwrap());
+            if next.len() == self.vertex_octtree.last().unwrap().len() {
+                break;
+            }
+            self.vertex_octtree.push(next);
+        }
+        let count = self.vertex_octtree.len();
+        self.finish_stage(stats, t, Stage::SubsampleOcttree, count)?;
+
+        let count = self.solve_qefs(collapse);
+        self.finish_stage(stats, t, Stage::SolveQefs, count)
+    }
+
+    // Tessellates the cells [cells[0], cells[1]) of the grid. Faces are generated for the edges
+    // whose lower end lies in the tile. Cells of other tiles around these edges are referenced
+    // by their VertexIndex, which the stitcher resolves through the vertices that the other
+    // tiles export for their upper boundary cells.
+    pub(crate) fn tessellate_tile(
+        &mut self,
+        tile: Index,
+        cells: [Index; 2],
+        levels: usize,
+    ) -> Result<TileMesh<S>, DualContouringError> {
+        self.check_grid()?;
+        self.tile = Some(TileRange { cells, levels });
+        let mut stats = TessellationStats::default();
+        let mut t = Timer::new();
+        self.progress = 0.;
+        self.clear();
+        let collapse = self.collapse();
+        self.build_vertex_octtree(&mut stats, &mut t, &collapse)?;
+
+        let mut numbering = NodeNumbering::new(&self.vertex_octtree);
+        let edges: Vec<EdgeIndex> = self
+            .sorted_edges()
+            .into_iter()
+            .filter(|edge_index| self.owns_edge(edge_index.index))
+            .collect();
+        let polygons = self
+            .polygons(&edges, &collapse)
+            .into_iter()
+            .map(|polygon| {
+                polygon
+                    .into_iter()
+                    .map(|corner| match corner {
+                        Corner::Node(layer, index) => {
+                            TileVertex::Own(numbering.number(layer, index))
+                        }
+                        Corner::Neighbor(vi) => TileVertex::Neighbor(vi),
+                    })
+                    .collect()
+            })
+            .collect();
+        self.check_cancelled()?;
+
+        let mut boundary: Vec<VertexIndex> = self
+            .vertex_index_map
+            .keys()
+            .filter(|vi| (0..3).any(|i| vi.index[i] + 1 == cells[1][i]))
+            .copied()
+            .collect();
+        boundary.sort_unstable_by_key(|vi| (vi.index, vi.edges.as_u32()));
+        let mut exports = HashMap::new();
+        for vertex_index in boundary {
+            if let Some((layer, index)) = self.lookup_vertex(vertex_index, &collapse) {
+                exports.insert(vertex_index, numbering.number(layer, index));
+            }
+        }
+        Ok(TileMesh {
+            tile,
+            vertices: self.node_positions(&numbering.nodes),
+            polygons,
+            exports,
+        })
+    }
+
+    // Returns true if the grid point is the lower end of edges that the tile generates faces for.
+    fn owns_edge(&self, idx: Index) -> bool {
+        let tile = self.tile.as_ref().unwrap();
+        (0..3).all(|i| {
+            tile.cells[0][i] <= idx[i]
+                && (idx[i] < tile.cells[1][i] || (self.clip && idx[i] == self.dim[i]))
+        })
+    }
+
+    // Returns the faces around the given edges as polygons of three or four corners.
+    fn polygons(&self, edges: &[EdgeIndex], collapse: &Collapse<S>) -> Vec<Vec<Corner>> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let iter = edges.par_iter();
+        #[cfg(target_arch = "wasm32")]
+        let iter = edges.iter();
+        iter.map(|&edge_index| self.polygon(edge_index, collapse))
+            .filter(|polygon| polygon.len() >= 3)
+            .collect()
+    }
+
+    // Returns the corners of the face around the edge, ordered by the orientation of the surface.
+    // Cells outside the tile become references into neighboring tiles.
+    fn polygon(&self, edge_index: EdgeIndex, collapse: &Collapse<S>) -> Vec<Corner> {
+        use crate::vertex_index::checked_neg_offset;
+        debug_assert!((edge_index.edge as usize) < 4);
+        let mut p = Vec::with_capacity(4);
+        for &quad_egde in &QUADS[edge_index.edge as usize] {
+            let Some(cell_idx) =
+                checked_neg_offset(edge_index.index, EDGE_OFFSET[quad_egde as usize])
+            else {
+                continue;
+            };
+            if !self.cell_in_grid(cell_idx) {
+                continue;
+            }
+            let corner = if self.cell_in_tile(cell_idx) {
+                match self.lookup_cell_point(quad_egde, cell_idx, collapse) {
+                    Some((layer, index)) => Corner::Node(layer, index),
+                    None => continue,
+                }
+            } else {
+                Corner::Neighbor(VertexIndex {
+                    edges: get_connected_edges(quad_egde, self.bitset_for_cell(cell_idx)),
+                    index: cell_idx,
+                })
+            };
+            if !p.contains(&corner) {
+                p.push(corner)
+            }
+        }
+        if p.len() < 3 {
+            return Vec::new();
+        }
+        if is_inside(self.eval_at_index(edge_index.index)) {
+            p.reverse();
+        }
+        p
+    }
+
+    // Returns the positions of the given octree nodes. Nodes that were skipped while solving the
+    // QEFs top down are solved here.
+    fn node_positions(&self, nodes: &[(usize, usize)]) -> Vec<[S; 3]> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let iter = nodes.par_iter();
+        #[cfg(target_arch = "wasm32")]
+        let iter = nodes.iter();
+        iter.map(|&(layer, index)| {
+            let mut qef = self.vertex_octtree[layer][index].qef.clone();
+            if qef.error.is_nan() {
+                self.solve_qef(&mut qef, layer);
+            }
+            [qef.solution.x, qef.solution.y, qef.solution.z]
+        })
+        .collect()
+    }
+
+    fn sample_value_grid(
+        &mut self,
+        idx: Index,
+        size: usize,
+        val: S,
+    ) -> Result<(), DualContouringError> {
+        debug_assert!(size > 1);
+        self.check_cancelled()?;
+        if let Some(ref tile) = self.tile {
+            // Skip cubes that do not touch the grid points of the tile.
+            if (0..3).any(|i| idx[i] > tile.cells[1][i] || idx[i] + size < tile.cells[0][i]) {
+                return Ok(());
+            }
+        }
+        let size = size / 2;
+
+        // The corners of the 8 sub cubes. The first one is idx, whose value is known.
+        let corners: [Index; 8] = std::array::from_fn(|i| {
+            [
+                idx[0] + (i & 1) * size,
+                idx[1] + (i >> 1 & 1) * size,
+                idx[2] + (i >> 2) * size,
+            ]
+        });
+        let positions = corners.map(|corner| self.position(corner));
+        let mut values = [val; 8];
+        self.value_batch(&positions[1..], &mut values[1..]);
+
+        for ((midx, mpos), value) in corners.into_iter().zip(positions).zip(values) {
+            // Otherwise zero counts as outside, like any positive value.
+            if value == From::from(0f32) && self.config.zero_handling == ZeroHandling::Restart {
+                return Err(DualContouringError::HitZero(format!("{}", mpos)));
+            }
+            if Float::is_nan(value) {
+                return Err(DualContouringError::NaNValue(format!("{}", mpos)));
+            }
+
+            if size > 1 && self.may_contain_surface(midx, size, value) {
+                self.sample_value_grid(midx, size, value)?;
+            } else {
+                self.value_grid.insert(midx, value);
+                self.sampled_volume += (size as f64).powi(3);
+                let fraction = self.sampled_volume / self.sampling_volume;
+                self.report_progress(Stage::SampleValueGrid, fraction as f32);
+            }
+        }
+        Ok(())
+    }
+
+    fn compact_value_grid(&mut self) {
+        let value_grid = &mut self.value_grid;
+        #[cfg(not(target_arch = "wasm32"))]
+        let iter = value_grid.par_iter();
         #[cfg(target_arch = "wasm32")]
         let iter = value_grid.iter();
         let keys_to_remove: Vec<_> = iter
@@ -614,7 +1801,7 @@ impl<'a, S: From<f32> + RealField + Float + AsUSize> ManifoldDualContouring<'a,
                         for x in 0..3 {
                             let adjacent_idx = [idx[0] + x - 1, idx[1] + y - 1, idx[2] + z - 1];
                             if let Some(&adjacent_value) = value_grid.get(&adjacent_idx) {
-                                if Float::signum(v) != Float::signum(adjacent_value) {
+                                if is_inside(v) != is_inside(adjacent_value) {
                                     return false;
                                 }
                             }
@@ -632,79 +1819,155 @@ impl<'a, S: From<f32> + RealField + Float + AsUSize> ManifoldDualContouring<'a,
     }
 
     fn generate_edge_grid(&mut self) {
-        let mut edge_grid = self.edge_grid.borrow_mut();
-        for (&point_idx, &point_value) in &self.value_grid {
-            for &edge in &[Edge::A, Edge::B, Edge::C] {
-                let mut adjacent_idx = point_idx;
-                adjacent_idx[edge as usize] += 1;
-                if let Some(&adjacent_value) = self.value_grid.get(&adjacent_idx) {
-                    let point_pos = self.origin
-                        + na::Vector3::new(
-                            From::from(point_idx[0] as f32),
-                            From::from(point_idx[1] as f32),
-                            From::from(point_idx[2] as f32),
-                        ) * self.res;
-                    let mut adjacent_pos = point_pos;
-                    adjacent_pos[edge as usize] += self.res;
-                    if let Some(plane) =
-                        self.find_zero(point_pos, point_value, adjacent_pos, adjacent_value)
-                    {
-                        edge_grid.insert(
-                            EdgeIndex {
-                                edge,
-                                index: point_idx,
-                            },
-                            plane,
-                        );
-                    }
+        #[cfg(not(target_arch = "wasm32"))]
+        let iter = self.value_grid.par_iter();
+        #[cfg(target_arch = "wasm32")]
+        let iter = self.value_grid.iter();
+        let (edges, crossings): (Vec<_>, Vec<_>) = iter
+            .flat_map(|(&point_idx, &point_value)| self.edges_at_point(point_idx, point_value))
+            .unzip();
+        // The normals are computed in batches once all crossings are known.
+        #[cfg(not(target_arch = "wasm32"))]
+        let chunks = crossings.par_chunks(NORMAL_BATCH_SIZE);
+        #[cfg(target_arch = "wasm32")]
+        let chunks = crossings.chunks(NORMAL_BATCH_SIZE);
+        let normals: Vec<_> = chunks.flat_map(|chunk| self.normal_batch(chunk)).collect();
+        self.edge_grid = edges
+            .into_iter()
+            .zip(crossings.into_iter().zip(normals))
+            .map(|(edge, (p, n))| (edge, Plane { p, n }))
+            .collect();
+    }
+
+    // Returns the crossings on the edges that start at the given grid point.
+    fn edges_at_point(&self, point_idx: Index, point_value: S) -> Vec<(EdgeIndex, na::Point3<S>)> {
+        let mut result = Vec::new();
+        for &edge in &[Edge::A, Edge::B, Edge::C] {
+            let mut adjacent_idx = point_idx;
+            adjacent_idx[edge as usize] += 1;
+            if self.clip && (0..3).any(|i| adjacent_idx[i] > self.dim[i]) {
+                continue;
+            }
+            if !self.point_in_tile(point_idx) || !self.point_in_tile(adjacent_idx) {
+                continue;
+            }
+            if let Some(&adjacent_value) = self.value_grid.get(&adjacent_idx) {
+                let point_pos = self.position(point_idx);
+                let mut adjacent_pos = point_pos;
+                adjacent_pos[edge as usize] += self.res;
+                if let Some(crossing) =
+                    self.find_zero(point_pos, point_value, adjacent_pos, adjacent_value)
+                {
+                    result.push((
+                        EdgeIndex {
+                            edge,
+                            index: point_idx,
+                        },
+                        crossing,
+                    ));
                 }
             }
         }
+        result
     }
 
-    fn solve_qefs(&self) -> usize {
+    // HashMap iteration order differs between runs, so anything that assigns indexes walks the
+    // edges in sorted order instead.
+    fn sorted_edges(&self) -> Vec<EdgeIndex> {
+        let mut edges: Vec<EdgeIndex> = self.edge_grid.keys().copied().collect();
+        edges.sort_unstable();
+        edges
+    }
+
+    // Solves the QEFs top down, one layer at a time. Children are only solved if the error of
+    // their parent exceeds the threshold, since the mesh does not use them otherwise.
+    fn solve_qefs(&mut self, collapse: &Collapse<S>) -> usize {
+        let mut octtree = std::mem::take(&mut self.vertex_octtree);
         let mut num_solved = 0;
-        if let Some(top_layer) = self.vertex_octtree.last() {
-            for i in 0..top_layer.len() {
-                num_solved += self.recursively_solve_qefs(&self.vertex_octtree.len() - 1, i);
-            }
+        for layer in (0..octtree.len()).rev() {
+            let (lower, upper) = octtree.split_at_mut(layer + 1);
+            let parents = upper.first();
+            #[cfg(not(target_arch = "wasm32"))]
+            let iter = lower[layer].par_iter_mut();
+            #[cfg(target_arch = "wasm32")]
+            let iter = lower[layer].iter_mut();
+            num_solved += iter
+                .map(|vertex| {
+                    if let Some(parents) = parents {
+                        let parent = &parents[vertex.parent.unwrap()];
+                        // Also false for unsolved parents, whose error is NaN.
+                        let too_coarse = Float::abs(parent.qef.error)
+                            > self.max_error(collapse, layer + 1, parent);
+                        if !too_coarse {
+                            return 0;
+                        }
+                    }
+                    debug_assert!(
+                        vertex.qef.error.is_nan(),
+                        "found solved qef layer {:?} {:?} parent: {:?}",
+                        layer,
+                        vertex.index,
+                        vertex.parent
+                    );
+                    self.solve_qef(&mut vertex.qef, layer);
+                    1
+                })
+                .sum::<usize>();
         }
+        self.vertex_octtree = octtree;
         num_solved
     }
 
-    fn recursively_solve_qefs(&self, layer: usize, index_in_layer: usize) -> usize {
-        let vertex = &self.vertex_octtree[layer][index_in_layer];
-        assert!(vertex.children.is_empty() || layer > 0);
-        let error;
-        {
-            let mut qef = vertex.qef.borrow_mut();
-            debug_assert!(
-                qef.error.is_nan(),
-                "found solved qef layer {:?} index {:?} {:?} parent: {:?}",
-                layer,
-                index_in_layer,
-                vertex.index,
-                vertex.parent
-            );
-            qef.solve();
-            error = qef.error;
+    fn solve_qef(&self, qef: &mut qef::Qef<S>, layer: usize) {
+        let cell_size = self.res * Float::powi(From::from(2f32), layer as i32);
+        let dilation = match self.config.qef_clamp {
+            QefClamp::Cell => S::zero(),
+            QefClamp::DilatedParent => cell_size / From::from(2f32),
+        };
+        qef.solve(
+            self.config.qef_truncation,
+            self.config.qef_regularization,
+            dilation,
+        );
+        if let Some(ref cap) = self.cap {
+            qef.solution = snap_to_box(cap, qef.solution, cell_size * From::from(SNAP_TOLERANCE));
         }
-        let mut num_solved = 1;
-        if Float::abs(error) > self.error {
-            for &child_index in &vertex.children {
-                num_solved += self.recursively_solve_qefs(layer - 1, child_index);
-            }
+        if let (true, Some(region)) = (self.clip, &self.config.region) {
+            // The grid ends up to a cell beyond the region, and merged cells reach beyond the
+            // grid.
+            qef.solution = snap_to_box(region, qef.solution, S::zero());
         }
-        num_solved
     }
 
     fn generate_leaf_vertices(&self) -> (Vec<Vertex<S>>, HashMap<VertexIndex, usize>) {
+        let edges = self.sorted_edges();
+        #[cfg(not(target_arch = "wasm32"))]
+        let iter = edges.par_iter();
+        #[cfg(target_arch = "wasm32")]
+        let iter = edges.iter();
+        let leafs_per_edge: Vec<_> = iter
+            .map(|edge_index| self.leafs_for_minimal_egde(edge_index))
+            .collect();
+        // Number the leafs in the order in which the sorted edges reach them.
         let mut index_map = HashMap::new();
-        let mut vertices = Vec::new();
-        for edge_index in self.edge_grid.borrow().keys() {
-            self.add_vertices_for_minimal_egde(edge_index, &mut vertices, &mut index_map);
+        let mut leafs = Vec::new();
+        for vertex_index in leafs_per_edge.into_iter().flatten() {
+            index_map.entry(vertex_index).or_insert_with(|| {
+                leafs.push(vertex_index);
+                leafs.len() - 1
+            });
         }
-        for vertex in &mut vertices {
+        #[cfg(not(target_arch = "wasm32"))]
+        let iter = leafs.par_iter();
+        #[cfg(target_arch = "wasm32")]
+        let iter = leafs.iter();
+        let mut vertices: Vec<_> = iter.map(|&vi| self.leaf_vertex(vi)).collect();
+
+        #[cfg(not(target_arch = "wasm32"))]
+        let iter = vertices.par_iter_mut();
+        #[cfg(target_arch = "wasm32")]
+        let iter = vertices.iter_mut();
+        iter.for_each(|vertex| {
             for neighbor_vec in &mut vertex.neighbors {
                 neighbor_vec.retain(|n| match n {
                     VarIndex::VertexIndex(vi) => index_map.contains_key(vi),
@@ -717,7 +1980,7 @@ impl<'a, S: From<f32> + RealField + Float + AsUSize> ManifoldDualContouring<'a,
                     }
                 }
             }
-        }
+        });
         for vi in 0..vertices.len() {
             for np in 0..vertices[vi].neighbors.len() {
                 for ni in 0..vertices[vi].neighbors[np].len() {
@@ -745,245 +2008,203 @@ impl<'a, S: From<f32> + RealField + Float + AsUSize> ManifoldDualContouring<'a,
         }
         (vertices, index_map)
     }
-    fn add_vertices_for_minimal_egde(
-        &self,
-        edge_index: &EdgeIndex,
-        vertices: &mut Vec<Vertex<S>>,
-        index_map: &mut HashMap<VertexIndex, usize>,
-    ) {
+    // Returns the leafs in the cells around the edge.
+    fn leafs_for_minimal_egde(&self, edge_index: &EdgeIndex) -> Vec<VertexIndex> {
         use crate::vertex_index::checked_neg_offset;
         debug_assert!((edge_index.edge as usize) < 4);
-        let cell_size = na::Vector3::new(self.res, self.res, self.res);
+        let mut result = Vec::with_capacity(4);
         for &quad_egde in &QUADS[edge_index.edge as usize] {
             let Some(idx) = checked_neg_offset(edge_index.index, EDGE_OFFSET[quad_egde as usize])
             else {
                 continue;
             };
-
-            let edge_set = get_connected_edges(quad_egde, self.bitset_for_cell(idx));
-            let vertex_index = VertexIndex {
-                edges: edge_set,
+            if !self.cell_in_grid(idx) || !self.cell_in_tile(idx) {
+                continue;
+            }
+            result.push(VertexIndex {
+                edges: get_connected_edges(quad_egde, self.bitset_for_cell(idx)),
                 index: idx,
-            };
-            index_map.entry(vertex_index).or_insert_with(|| {
-                let mut neighbors = [
-                    Vec::new(),
-                    Vec::new(),
-                    Vec::new(),
-                    Vec::new(),
-                    Vec::new(),
-                    Vec::new(),
-                ];
-                for (i, neighbor) in neighbors.iter_mut().enumerate().take(6) {
-                    if let Some(mut neighbor_index) = vertex_index.neighbor(i) {
-                        for edges in get_connected_edges_from_edge_set(
-                            neighbor_index.edges,
-                            self.bitset_for_cell(neighbor_index.index),
-                        ) {
-                            neighbor_index.edges = edges;
-                            let idx = VarIndex::VertexIndex(neighbor_index);
-                            if !neighbor.contains(&idx) {
-                                neighbor.push(idx);
-                            }
-                        }
+            });
+        }
+        result
+    }
+
+    fn leaf_vertex(&self, vertex_index: VertexIndex) -> Vertex<S> {
+        let idx = vertex_index.index;
+        let edge_set = vertex_index.edges;
+        let mut neighbors = [
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+            Vec::new(),
+        ];
+        for (i, neighbor) in neighbors.iter_mut().enumerate().take(6) {
+            if let Some(mut neighbor_index) = vertex_index.neighbor(i) {
+                for edges in get_connected_edges_from_edge_set(
+                    neighbor_index.edges,
+                    self.bitset_for_cell(neighbor_index.index),
+                ) {
+                    neighbor_index.edges = edges;
+                    let idx = VarIndex::VertexIndex(neighbor_index);
+                    if !neighbor.contains(&idx) {
+                        neighbor.push(idx);
                     }
                 }
-                let mut intersections = [0u32; 12];
-                for edge in edge_set {
-                    intersections[edge] = 1;
-                }
-                let tangent_planes: Vec<_> = edge_set
-                    .map(|edge| {
-                        self.get_edge_tangent_plane(&EdgeIndex {
-                            edge: Edge::from_usize(edge),
-                            index: idx,
-                        })
-                    })
-                    .collect();
-                let cell_origin = self.origin
-                    + na::Vector3::new(
-                        From::from(idx[0] as f32),
-                        From::from(idx[1] as f32),
-                        From::from(idx[2] as f32),
-                    ) * self.res;
-                vertices.push(Vertex {
+            }
+        }
+        let mut intersections = [0u32; 12];
+        for edge in edge_set {
+            intersections[edge] = 1;
+        }
+        let tangent_planes: Vec<_> = edge_set
+            .map(|edge| {
+                self.get_edge_tangent_plane(&EdgeIndex {
+                    edge: Edge::from_usize(edge),
                     index: idx,
-                    qef: RefCell::new(qef::Qef::new(
-                        &tangent_planes,
-                        BoundingBox::new(&cell_origin, &(cell_origin + cell_size)),
-                    )),
-                    neighbors,
-                    parent: Cell::new(None),
-                    children: Vec::new(),
-                    mesh_index: Cell::new(None),
-                    edge_intersections: intersections,
-                    euler_characteristic: 1,
-                });
-                vertices.len() - 1
-            });
+                })
+            })
+            .collect();
+        let cell_origin = self.position(idx);
+        let cell_size = na::Vector3::new(self.res, self.res, self.res);
+        Vertex {
+            index: idx,
+            qef: qef::Qef::new(
+                &tangent_planes,
+                BoundingBox::new(&cell_origin, &(cell_origin + cell_size)),
+            ),
+            neighbors,
+            parent: None,
+            children: Vec::new(),
+            edge_intersections: intersections,
+            euler_characteristic: 1,
+            face_contours: leaf_face_contours(edge_set, self.bitset_for_cell(idx)),
+            manifold: true,
         }
     }
 
     fn get_edge_tangent_plane(&self, edge_index: &EdgeIndex) -> Plane<S> {
         let base = edge_index.base();
-        if let Some(plane) = self.edge_grid.borrow().get(&base) {
+        if let Some(plane) = self.edge_grid.get(&base) {
             return *plane;
         }
-        let a_pos = self.origin
-            + na::Vector3::new(
-                From::from(base.index[0] as f32),
-                From::from(base.index[1] as f32),
-                From::from(base.index[2] as f32),
-            ) * self.res;
+        let a_pos = self.position(base.index);
         let mut b_pos = a_pos;
         b_pos[base.edge as usize] += self.res;
-        let av = self.function.value(&a_pos);
-        let bv = self.function.value(&b_pos);
-        if let Some(plane) = self.find_zero(a_pos, av, b_pos, bv) {
-            return plane;
-        }
-        // No sign change: return a plane at the midpoint with a default normal.
+        let mut values = [S::zero(); 2];
+        self.value_batch(&[a_pos, b_pos], &mut values);
+        // Without a sign change, use the midpoint with a default normal.
+        let p = self
+            .find_zero(a_pos, values[0], b_pos, values[1])
+            .unwrap_or_else(|| na::center(&a_pos, &b_pos));
         Plane {
-            p: na::center(&a_pos, &b_pos),
-            n: self.function.normal(&na::center(&a_pos, &b_pos)),
+            p,
+            n: self.normal(&p),
         }
     }
 
-    fn lookup_cell_point(&self, edge: Edge, idx: Index) -> Option<usize> {
+    fn lookup_cell_point(
+        &self,
+        edge: Edge,
+        idx: Index,
+        collapse: &Collapse<S>,
+    ) -> Option<(usize, usize)> {
         let edge_set = get_connected_edges(edge, self.bitset_for_cell(idx));
-        let vertex_index = VertexIndex {
-            edges: edge_set,
-            index: idx,
-        };
+        self.lookup_vertex(
+            VertexIndex {
+                edges: edge_set,
+                index: idx,
+            },
+            collapse,
+        )
+    }
 
+    // Returns layer and index of the octree node that represents the given leaf in the mesh.
+    fn lookup_vertex(
+        &self,
+        vertex_index: VertexIndex,
+        collapse: &Collapse<S>,
+    ) -> Option<(usize, usize)> {
         let mut octtree_index = *self.vertex_index_map.get(&vertex_index)?;
         let mut octtree_layer = 0;
-        loop {
+        while self.vertex_octtree.len() > 1 {
             let next_index = self.vertex_octtree[octtree_layer][octtree_index]
                 .parent
-                .get()
                 .unwrap();
             let next_vertex = &self.vertex_octtree[octtree_layer + 1][next_index];
-            let error = next_vertex.qef.borrow().error;
-            if (!error.is_nan() && error > (self.error))
+            let error = next_vertex.qef.error;
+            if (!error.is_nan() && error > self.max_error(collapse, octtree_layer + 1, next_vertex))
                 || (octtree_layer == self.vertex_octtree.len() - 2)
-                || !next_vertex.is_2manifold()
+                || (octtree_layer >= collapse.max_layer)
+                || collapse
+                    .rejected
+                    .is_some_and(|rejected| rejected[octtree_layer + 1][next_index])
+                || (self.config.manifold_checks && !next_vertex.manifold)
             {
                 break;
             }
             octtree_layer += 1;
             octtree_index = next_index;
         }
-        let vertex = &self.vertex_octtree[octtree_layer][octtree_index];
-        if let Some(mesh_index) = vertex.mesh_index.get() {
-            return Some(mesh_index);
-        }
-        if vertex.qef.borrow().error.is_nan() {
-            vertex.qef.borrow_mut().solve()
-        }
-        let qef_solution = vertex.qef.borrow().solution;
-        let vertex_list = &mut self.mesh.borrow_mut().vertices;
-        let result = vertex_list.len();
-        vertex.mesh_index.set(Some(result));
-        vertex_list.push([qef_solution.x, qef_solution.y, qef_solution.z]);
-        Some(result)
+        Some((octtree_layer, octtree_index))
     }
 
     fn eval_at_index(&self, idx: Index) -> S {
         if let Some(&v) = self.value_grid.get(&idx) {
             return v;
         }
-        let pos = self.origin
-            + na::Vector3::new(
-                From::from(idx[0] as f32),
-                From::from(idx[1] as f32),
-                From::from(idx[2] as f32),
-            ) * self.res;
-        self.function.value(&pos)
+        self.value(&self.position(idx))
     }
 
     fn bitset_for_cell(&self, idx: Index) -> BitSet {
-        let mut idx = idx;
-        let mut result = BitSet::zero();
-        for z in 0..2 {
-            for y in 0..2 {
-                for x in 0..2 {
-                    if self.eval_at_index(idx) < From::from(0f32) {
-                        result.set(z << 2 | y << 1 | x);
-                    }
-                    idx[0] += 1;
+        let mut values = [S::zero(); 8];
+        // Corners that were dropped from the value grid are evaluated in one batch.
+        let mut missing = Vec::new();
+        let mut positions = Vec::new();
+        for (i, value) in values.iter_mut().enumerate() {
+            let corner = [idx[0] + (i & 1), idx[1] + (i >> 1 & 1), idx[2] + (i >> 2)];
+            match self.value_grid.get(&corner) {
+                Some(&v) => *value = v,
+                None => {
+                    missing.push(i);
+                    positions.push(self.position(corner));
                 }
-                idx[0] -= 2;
-                idx[1] += 1;
             }
-            idx[1] -= 2;
-            idx[2] += 1;
         }
-        result
-    }
-
-    fn compute_quad(&self, edge_index: EdgeIndex) {
-        use crate::vertex_index::checked_neg_offset;
-        debug_assert!((edge_index.edge as usize) < 4);
-
-        let mut p = Vec::with_capacity(4);
-        for &quad_egde in &QUADS[edge_index.edge as usize] {
-            let Some(cell_idx) =
-                checked_neg_offset(edge_index.index, EDGE_OFFSET[quad_egde as usize])
-            else {
-                continue;
-            };
-            let Some(point_index) = self.lookup_cell_point(quad_egde, cell_idx) else {
-                continue;
-            };
-            if !p.contains(&point_index) {
-                p.push(point_index)
+        if !missing.is_empty() {
+            let mut missing_values = vec![S::zero(); missing.len()];
+            self.value_batch(&positions, &mut missing_values);
+            for (i, v) in missing.into_iter().zip(missing_values) {
+                values[i] = v;
             }
         }
-        if p.len() < 3 {
-            return;
-        }
-        if self.eval_at_index(edge_index.index) < From::from(0f32) {
-            p.reverse();
-        }
-        let face_list = &mut self.mesh.borrow_mut().faces;
-        face_list.push([p[0], p[1], p[2]]);
-        if p.len() == 4 {
-            face_list.push([p[2], p[3], p[0]]);
+        let mut result = BitSet::zero();
+        for (i, value) in values.into_iter().enumerate() {
+            if is_inside(value) {
+                result.set(i);
+            }
         }
+        result
     }
 
-    fn find_zero(&self, a: na::Point3<S>, av: S, b: na::Point3<S>, bv: S) -> Option<Plane<S>> {
+    // Returns the crossing of the surface between a and b, if their values differ in sign.
+    fn find_zero(&self, a: na::Point3<S>, av: S, b: na::Point3<S>, bv: S) -> Option<na::Point3<S>> {
         assert!(a != b);
-        if Float::signum(av) == Float::signum(bv) {
+        if is_inside(av) == is_inside(bv) {
             return None;
         }
-        let d = a - b;
-        let mut distance = Float::max(
-            Float::max(Float::abs(d.x), Float::abs(d.y)),
-            Float::abs(d.z),
-        );
-        distance = Float::min(Float::min(distance, Float::abs(av)), Float::abs(bv));
-        let precision: S = From::from(PRECISION);
-        if distance < precision * self.res {
-            let result = if Float::abs(bv) < Float::abs(av) {
-                &b
-            } else {
-                &a
-            };
-            return Some(Plane {
-                p: *result,
-                n: self.function.normal(result),
-            });
-        }
-        let n = a + (b - a) * (Float::abs(av) / Float::abs(bv - av));
-        let nv = self.function.value(&n);
-
-        if Float::signum(av) != Float::signum(nv) {
-            self.find_zero(a, av, n, nv)
-        } else {
-            self.find_zero(n, nv, b, bv)
-        }
+        Some(root_finder::find_root(
+            self.config.root_finder,
+            a,
+            av,
+            b,
+            bv,
+            self.config.absolute_precision + self.config.precision * self.res,
+            self.config.max_root_iterations,
+            |p| self.value(p),
+            |p| self.value_and_gradient(p),
+        ))
     }
 }
 
@@ -1030,6 +2251,93 @@ mod tests {
         assert!(connected_edges.contains(&BitSet::from_4bits(3, 3, 4, 11)));
     }
 
+    // Merges the leaf vertices of a block of 2x2x2 cells, given which of its 3x3x3 grid points
+    // are inside.
+    fn merge_cells(inside: impl Fn([usize; 3]) -> bool) -> super::Vertex<f64> {
+        use super::{leaf_face_contours, Vertex, CELL_CONFIGS};
+        let vertex = |index, edge_intersections, euler_characteristic, face_contours| Vertex {
+            index,
+            qef: crate::qef::Qef::new(&[], super::BoundingBox::neg_infinity()),
+            neighbors: Default::default(),
+            parent: None,
+            children: Vec::new(),
+            edge_intersections,
+            euler_characteristic,
+            face_contours,
+            manifold: true,
+        };
+        let mut leaves = Vec::new();
+        for i in 0..8 {
+            let index = [i & 1, i >> 1 & 1, i >> 2];
+            let mut cell = BitSet::zero();
+            for corner in 0..8 {
+                if inside([
+                    index[0] + (corner & 1),
+                    index[1] + (corner >> 1 & 1),
+                    index[2] + (corner >> 2),
+                ]) {
+                    cell.set(corner);
+                }
+            }
+            for &edges in CELL_CONFIGS[cell.as_u32() as usize] {
+                let mut intersections = [0; 12];
+                for edge in edges {
+                    intersections[edge] = 1;
+                }
+                let contours = leaf_face_contours(edges, cell);
+                leaves.push(vertex(index, intersections, 1, contours));
+            }
+        }
+        let children = (0..leaves.len()).collect();
+        let (intersections, euler) = super::subsample_euler_characteristics(&children, &leaves);
+        let contours = super::subsample_face_contours(&children, &leaves);
+        vertex([0; 3], intersections, euler, contours)
+    }
+
+    #[test]
+    fn leaf_face_contours() {
+        // Corners 0 and 3 are diagonal on face 4, but connected through the corners above.
+        let cell = BitSet::from_u32(0b1101_1001);
+        let patches = super::CELL_CONFIGS[cell.as_u32() as usize];
+        assert_eq!(patches.len(), 1);
+        let contours = super::leaf_face_contours(patches[0], cell);
+        assert_eq!(contours[4].paths, vec![[0, 1], [3, 4]]);
+        assert!(!contours[4].tangled);
+        assert_eq!(contours[5].paths, vec![[6, 10]]);
+    }
+
+    #[test]
+    fn merged_cell_topology() {
+        // A plane through the lower half of the cells.
+        let plane = merge_cells(|p| p[2] == 0);
+        assert!(plane.is_2manifold());
+        assert_eq!(plane.euler_characteristic, 1);
+        for face in 0..4 {
+            assert_eq!(plane.face_contours[face].paths.len(), 1);
+        }
+        assert_eq!(plane.face_contours[4], super::FaceContour::default());
+        assert_eq!(plane.face_contours[5], super::FaceContour::default());
+
+        // A cup around the center of the bottom face is a disk, but meets that face in a loop.
+        let cup = merge_cells(|p| p == [1, 1, 0]);
+        assert_eq!(cup.euler_characteristic, 1);
+        assert_eq!(cup.face_contours[4].loops, 1);
+        assert!(!cup.is_2manifold());
+
+        // A bump around the middle of edge 0 meets its faces in single curves, but crosses the
+        // edge twice.
+        let bump = merge_cells(|p| p == [1, 0, 0]);
+        assert_eq!(bump.face_contours[2].paths, vec![[0, 0]]);
+        assert_eq!(bump.face_contours[4].paths, vec![[0, 0]]);
+        assert_eq!(bump.edge_intersections[0], 2);
+        assert!(!bump.is_2manifold());
+
+        // Two planes are not a disk.
+        let planes = merge_cells(|p| p[2] == 0 || p[2] == 2);
+        assert_eq!(planes.euler_characteristic, 2);
+        assert!(!planes.is_2manifold());
+    }
+
     struct UnitSphere {
         bbox: super::BoundingBox<f64>,
     }
@@ -1051,7 +2359,7 @@ mod tests {
         fn value(&self, p: &na::Point3<f64>) -> f64 {
             na::Vector3::new(p.x, p.y, p.z).norm() - 1.0
         }
-        fn normal(&self, p: &na::Point3<f64>) -> na::Vector3<f64> {
+        fn normal(&self, p: &na::Point3<f64>, _epsilon: f64) -> na::Vector3<f64> {
             na::Vector3::new(p.x, p.y, p.z).normalize()
         }
     }
@@ -1070,8 +2378,7 @@ mod tests {
     }
 
     #[test]
-    #[ignore]
-    // This test exposes https://github.com/hmeyer/tessellation/issues/7
+    // This test exposed https://github.com/hmeyer/tessellation/issues/7
     fn unit_sphere_with_simplification() -> Result<(), crate::mesh::MeshError> {
         let sphere = UnitSphere::new();
         let mut mdc = super::ManifoldDualContouring::new(&sphere, 0.2, 0.1);
@@ -1084,6 +2391,854 @@ mod tests {
         mesh.is_closed()
     }
 
+    // A unit sphere that evaluates to exactly zero on the first grid point.
+    struct ZeroAtOrigin {
+        sphere: UnitSphere,
+        zero: na::Point3<f64>,
+    }
+    impl ZeroAtOrigin {
+        fn new(res: f64) -> ZeroAtOrigin {
+            let sphere = UnitSphere::new();
+            let zero = sphere.bbox.dilate(1. + res * f64::from(1.1f32)).min;
+            ZeroAtOrigin { sphere, zero }
+        }
+    }
+
+    impl super::ImplicitFunction<f64> for ZeroAtOrigin {
+        fn bbox(&self) -> &super::BoundingBox<f64> {
+            &self.sphere.bbox
+        }
+        fn value(&self, p: &na::Point3<f64>) -> f64 {
+            if *p == self.zero {
+                return 0.;
+            }
+            self.sphere.value(p)
+        }
+        fn normal(&self, p: &na::Point3<f64>, epsilon: f64) -> na::Vector3<f64> {
+            self.sphere.normal(p, epsilon)
+        }
+    }
+
+    // A torus that counts how many points are evaluated one by one and in batches.
+    struct BatchCounter {
+        torus: crate::sdf::Torus<f64>,
+        single: std::sync::atomic::AtomicUsize,
+        batched: std::sync::atomic::AtomicUsize,
+    }
+    impl super::ImplicitFunction<f64> for BatchCounter {
+        fn bbox(&self) -> &super::BoundingBox<f64> {
+            self.torus.bbox()
+        }
+        fn value(&self, p: &na::Point3<f64>) -> f64 {
+            self.single
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.torus.value(p)
+        }
+        fn normal(&self, p: &na::Point3<f64>, epsilon: f64) -> na::Vector3<f64> {
+            self.single
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.torus.normal(p, epsilon)
+        }
+        fn value_batch(&self, points: &[na::Point3<f64>], values: &mut [f64]) {
+            self.batched
+                .fetch_add(points.len(), std::sync::atomic::Ordering::Relaxed);
+            self.torus.value_batch(points, values)
+        }
+        fn normal_batch(
+            &self,
+            points: &[na::Point3<f64>],
+            normals: &mut [na::Vector3<f64>],
+            epsilon: f64,
+        ) {
+            self.batched
+                .fetch_add(points.len(), std::sync::atomic::Ordering::Relaxed);
+            self.torus.normal_batch(points, normals, epsilon)
+        }
+        fn value_interval(&self, bbox: &super::BoundingBox<f64>) -> (f64, f64) {
+            self.torus.value_interval(bbox)
+        }
+    }
+
+    #[test]
+    fn batches_evaluations() {
+        use std::sync::atomic::Ordering;
+        let f = BatchCounter {
+            torus: crate::sdf::Torus::new(1.0, 0.3),
+            single: Default::default(),
+            batched: Default::default(),
+        };
+        let mesh = super::ManifoldDualContouring::new(&f, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let reference = super::ManifoldDualContouring::new(&f.torus, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh, reference);
+        // Only the grid origin and the bisection steps of find_zero are evaluated one by one.
+        let single = f.single.load(Ordering::Relaxed);
+        let batched = f.batched.load(Ordering::Relaxed);
+        assert!(batched > single, "{} batched, {} single", batched, single);
+    }
+
+    // A small sphere whose values grow much faster than the distance to its surface.
+    struct Steep {
+        sphere: crate::sdf::Sphere<f64>,
+        bbox: super::BoundingBox<f64>,
+        bounded: bool,
+        lipschitz: f64,
+    }
+    impl super::ImplicitFunction<f64> for Steep {
+        fn bbox(&self) -> &super::BoundingBox<f64> {
+            &self.bbox
+        }
+        fn value(&self, p: &na::Point3<f64>) -> f64 {
+            50. * self.sphere.value(p)
+        }
+        fn normal(&self, p: &na::Point3<f64>, epsilon: f64) -> na::Vector3<f64> {
+            self.sphere.normal(p, epsilon)
+        }
+        fn value_interval(&self, bbox: &super::BoundingBox<f64>) -> (f64, f64) {
+            if !self.bounded {
+                return (f64::MIN, f64::MAX);
+            }
+            let (lower, upper) = self.sphere.value_interval(bbox);
+            (50. * lower, 50. * upper)
+        }
+        fn lipschitz(&self) -> f64 {
+            self.lipschitz
+        }
+    }
+
+    #[test]
+    fn prunes_with_intervals() -> Result<(), crate::mesh::MeshError> {
+        let mut f = Steep {
+            sphere: crate::sdf::Sphere::new(0.3),
+            bbox: super::BoundingBox::new(
+                &na::Point3::new(-2., -2., -2.),
+                &na::Point3::new(2., 2., 2.),
+            ),
+            bounded: false,
+            lipschitz: 1.,
+        };
+        // Distance based pruning skips the cubes around the sphere.
+        assert!(matches!(
+            super::ManifoldDualContouring::new(&f, 0.1, 0.1).tessellate(),
+            Err(super::DualContouringError::EmptySurface)
+        ));
+        f.bounded = true;
+        let mesh = super::ManifoldDualContouring::new(&f, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        mesh.is_closed()
+    }
+
+    #[test]
+    fn prunes_with_lipschitz_constant() -> Result<(), crate::mesh::MeshError> {
+        let mut f = Steep {
+            sphere: crate::sdf::Sphere::new(0.3),
+            bbox: super::BoundingBox::new(
+                &na::Point3::new(-2., -2., -2.),
+                &na::Point3::new(2., 2., 2.),
+            ),
+            bounded: false,
+            lipschitz: 50.,
+        };
+        let declared = super::ManifoldDualContouring::new(&f, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        declared.is_closed()?;
+        // Normalize finds the constant on its own.
+        f.lipschitz = 1.;
+        let normalized = crate::sdf::Normalize::new(f);
+        assert!(normalized.gradient_bound() >= 50.);
+        let mesh = super::ManifoldDualContouring::new(&normalized, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.faces.len(), declared.faces.len());
+        mesh.is_closed()
+    }
+
+    // A unit sphere that relies on the default normal.
+    struct NumericalNormals(UnitSphere);
+    impl super::ImplicitFunction<f64> for NumericalNormals {
+        fn bbox(&self) -> &super::BoundingBox<f64> {
+            &self.0.bbox
+        }
+        fn value(&self, p: &na::Point3<f64>) -> f64 {
+            self.0.value(p)
+        }
+    }
+
+    #[test]
+    fn default_normals() -> Result<(), crate::mesh::MeshError> {
+        let f = NumericalNormals(UnitSphere::new());
+        let analytic = super::ManifoldDualContouring::new(&f.0, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        let mesh = super::ManifoldDualContouring::new(&f, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.faces.len(), analytic.faces.len());
+        for (v, a) in mesh.vertices.iter().zip(&analytic.vertices) {
+            assert_relative_eq!(na::Vector3::from(*v), na::Vector3::from(*a), epsilon = 1e-3);
+        }
+        mesh.is_closed()
+    }
+
+    #[test]
+    fn retries_after_hitting_zero() {
+        use crate::{TessellationConfig, ZeroHandling};
+        let f = ZeroAtOrigin::new(0.2);
+        let config = TessellationConfig::default().zero_handling(ZeroHandling::Restart);
+        let mut mdc = super::ManifoldDualContouring::with_config(&f, 0.2, 0.0, config.clone());
+        let (mesh, stats) = mdc.tessellate_with_stats().unwrap();
+        assert_eq!(stats.retries, 1);
+        // Every call starts from the original grid again.
+        let (again, stats) = mdc.tessellate_with_stats().unwrap();
+        assert_eq!(stats.retries, 1);
+        assert_eq!(mesh, again);
+        let config = config.max_retries(0);
+        let mut mdc = super::ManifoldDualContouring::with_config(&f, 0.2, 0.0, config);
+        assert!(matches!(
+            mdc.tessellate(),
+            Err(super::DualContouringError::RetriesExhausted(1))
+        ));
+    }
+
+    #[test]
+    fn perturbs_zeros() -> Result<(), crate::mesh::MeshError> {
+        use crate::{TessellationConfig, ZeroHandling};
+        // Without padding cells, the faces of the cube lie exactly on grid planes.
+        let cube = crate::sdf::RoundedBox::<f64>::new(na::Vector3::new(0.5, 0.5, 0.5), 0.0);
+        let config = TessellationConfig::default()
+            .bbox_padding(0.5)
+            .bbox_padding_cells(0.);
+        let mut mdc = super::ManifoldDualContouring::with_config(&cube, 0.25, 0.0, config.clone());
+        let (mesh, stats) = mdc.tessellate_with_stats().unwrap();
+        assert_eq!(stats.retries, 0);
+        // Zeros count as outside, so the surface passes through the grid points on the faces.
+        for v in &mesh.vertices {
+            let distance = v.iter().fold(0f64, |m, c| m.max(c.abs()));
+            assert!((distance - 0.5).abs() < 0.01, "{:?} is not on the cube", v);
+        }
+        mesh.is_closed()?;
+
+        let config = config.zero_handling(ZeroHandling::Restart);
+        let mut mdc = super::ManifoldDualContouring::with_config(&cube, 0.25, 0.0, config);
+        let (mesh, stats) = mdc.tessellate_with_stats().unwrap();
+        assert!(stats.retries > 0);
+        mesh.is_closed()
+    }
+
+    struct Constant {
+        value: f64,
+        bbox: super::BoundingBox<f64>,
+    }
+    impl super::ImplicitFunction<f64> for Constant {
+        fn bbox(&self) -> &super::BoundingBox<f64> {
+            &self.bbox
+        }
+        fn value(&self, _: &na::Point3<f64>) -> f64 {
+            self.value
+        }
+        fn normal(&self, _: &na::Point3<f64>, _: f64) -> na::Vector3<f64> {
+            na::Vector3::new(0., 0., 1.)
+        }
+    }
+
+    #[test]
+    fn reports_errors() {
+        let unit = super::BoundingBox::new(
+            &na::Point3::new(-1., -1., -1.),
+            &na::Point3::new(1., 1., 1.),
+        );
+        let f = Constant {
+            value: 1.,
+            bbox: unit.clone(),
+        };
+        let mut mdc = super::ManifoldDualContouring::new(&f, 0.2, 0.0);
+        assert!(matches!(
+            mdc.tessellate(),
+            Err(super::DualContouringError::EmptySurface)
+        ));
+        let f = Constant {
+            value: f64::NAN,
+            bbox: unit.clone(),
+        };
+        let mut mdc = super::ManifoldDualContouring::new(&f, 0.2, 0.0);
+        assert!(matches!(
+            mdc.tessellate(),
+            Err(super::DualContouringError::NaNValue(_))
+        ));
+        let f = Constant {
+            value: 1.,
+            bbox: super::BoundingBox::new(
+                &na::Point3::new(-1., -1., -1.),
+                &na::Point3::new(1., f64::INFINITY, 1.),
+            ),
+        };
+        let mut mdc = super::ManifoldDualContouring::new(&f, 0.2, 0.0);
+        assert!(matches!(
+            mdc.tessellate(),
+            Err(super::DualContouringError::DegenerateBoundingBox(_))
+        ));
+    }
+
+    #[test]
+    fn deterministic_output() {
+        use crate::{TessellationConfig, ZeroHandling};
+        let f = ZeroAtOrigin::new(0.2);
+        let config = TessellationConfig::default().zero_handling(ZeroHandling::Restart);
+        let mut a = super::ManifoldDualContouring::with_config(&f, 0.2, 0.1, config.clone());
+        let mut b = super::ManifoldDualContouring::with_config(&f, 0.2, 0.1, config.clone());
+        let (mesh, stats) = a.tessellate_with_stats().unwrap();
+        assert!(stats.retries > 0);
+        assert_eq!(mesh, b.tessellate().unwrap());
+        assert_eq!(mesh, a.tessellate().unwrap());
+        let config = config.seed(17);
+        let mut a = super::ManifoldDualContouring::with_config(&f, 0.2, 0.1, config.clone());
+        let mut b = super::ManifoldDualContouring::with_config(&f, 0.2, 0.1, config);
+        let (seeded, stats) = a.tessellate_with_stats().unwrap();
+        assert!(stats.retries > 0);
+        assert_eq!(seeded, b.tessellate().unwrap());
+        // Another seed shifts the grid differently.
+        assert_ne!(seeded, mesh);
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn independent_of_thread_count() {
+        let f = crate::sdf::Torus::new(1.0, 0.3);
+        let tessellate = |threads| {
+            let pool = rayon::ThreadPoolBuilder::new()
+                .num_threads(threads)
+                .build()
+                .unwrap();
+            pool.install(|| {
+                super::ManifoldDualContouring::new(&f, 0.1, 0.1)
+                    .tessellate()
+                    .unwrap()
+            })
+        };
+        assert_eq!(tessellate(1), tessellate(4));
+    }
+
+    #[test]
+    fn concurrent_batch() {
+        use rayon::prelude::*;
+        let torus = crate::sdf::Torus::<f64>::new(1.0, 0.3);
+        let resolutions = [0.1, 0.15, 0.2, 0.25];
+        let serial: Vec<_> = resolutions
+            .iter()
+            .map(|&res| {
+                super::ManifoldDualContouring::new(&torus, res, 0.1)
+                    .tessellate()
+                    .unwrap()
+            })
+            .collect();
+        // Tessellators for a shared function are built on one thread and run on others.
+        let tessellators: Vec<_> = resolutions
+            .iter()
+            .map(|&res| super::ManifoldDualContouring::new(&torus, res, 0.1))
+            .collect();
+        let concurrent: Vec<_> = tessellators
+            .into_par_iter()
+            .map(|mut mdc| mdc.tessellate().unwrap())
+            .collect();
+        assert_eq!(serial, concurrent);
+    }
+
+    #[derive(Default)]
+    struct StageRecorder {
+        stages: std::sync::Mutex<Vec<super::Stage>>,
+        retries: std::sync::atomic::AtomicUsize,
+    }
+    impl crate::TessellationObserver for StageRecorder {
+        fn stage_finished(&self, event: &super::StageEvent) {
+            self.stages.lock().unwrap().push(event.stage);
+        }
+        fn retry(&self, _: &super::DualContouringError) {
+            self.retries
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+        }
+    }
+
+    #[test]
+    fn reports_stages_and_stats() {
+        use super::Stage;
+        let f = ZeroAtOrigin::new(0.2);
+        let recorder = StageRecorder::default();
+        let config =
+            crate::TessellationConfig::default().zero_handling(crate::ZeroHandling::Restart);
+        let mut mdc = super::ManifoldDualContouring::with_config(&f, 0.2, 0.1, config);
+        mdc.set_observer(&recorder);
+        let (mesh, stats) = mdc.tessellate_with_stats().unwrap();
+        let all_stages = [
+            Stage::SampleValueGrid,
+            Stage::CompactValueGrid,
+            Stage::EdgeGrid,
+            Stage::LeafVertices,
+            Stage::SubsampleOcttree,
+            Stage::SolveQefs,
+            Stage::GenerateQuads,
+        ];
+        assert_eq!(
+            recorder.retries.load(std::sync::atomic::Ordering::Relaxed),
+            1
+        );
+        assert_eq!(stats.retries, 1);
+        // The first attempt stops during sampling.
+        assert_eq!(recorder.stages.lock().unwrap()[..], all_stages);
+        let stages: Vec<_> = stats.stages.iter().map(|e| e.stage).collect();
+        assert_eq!(stages, all_stages);
+        assert_eq!(stats.faces, mesh.faces.len());
+        assert_eq!(stats.vertices, mesh.vertices.len());
+        assert_eq!(
+            stats.stage(Stage::GenerateQuads).unwrap().count,
+            mesh.faces.len()
+        );
+    }
+
+    // Cancels the token as soon as the given fraction of work is done.
+    struct CancelAt {
+        fraction: f32,
+        token: crate::CancellationToken,
+        progress: std::sync::Mutex<Vec<f32>>,
+    }
+    impl crate::TessellationObserver for CancelAt {
+        fn progress(&self, fraction: f32) {
+            self.progress.lock().unwrap().push(fraction);
+            if fraction >= self.fraction {
+                self.token.cancel();
+            }
+        }
+    }
+
+    #[test]
+    fn reports_progress() {
+        let sphere = UnitSphere::new();
+        let observer = CancelAt {
+            fraction: 2.,
+            token: crate::CancellationToken::new(),
+            progress: std::sync::Mutex::new(Vec::new()),
+        };
+        let mut mdc = super::ManifoldDualContouring::new(&sphere, 0.2, 0.1);
+        mdc.set_observer(&observer);
+        mdc.set_cancellation_token(observer.token.clone());
+        mdc.tessellate().unwrap();
+        let progress = observer.progress.lock().unwrap();
+        assert!(progress.len() > 10);
+        assert!(progress.windows(2).all(|w| w[0] < w[1]), "{:?}", progress);
+        assert_eq!(progress.last(), Some(&1.));
+    }
+
+    #[test]
+    fn cancellation() {
+        let sphere = UnitSphere::new();
+        let token = crate::CancellationToken::new();
+        token.cancel();
+        let mut mdc = super::ManifoldDualContouring::new(&sphere, 0.2, 0.1);
+        mdc.set_cancellation_token(token);
+        assert!(matches!(
+            mdc.tessellate(),
+            Err(super::DualContouringError::Cancelled)
+        ));
+
+        for fraction in [0.1, 0.5, 0.9] {
+            let observer = CancelAt {
+                fraction,
+                token: crate::CancellationToken::new(),
+                progress: std::sync::Mutex::new(Vec::new()),
+            };
+            let mut mdc = super::ManifoldDualContouring::new(&sphere, 0.2, 0.1);
+            mdc.set_observer(&observer);
+            mdc.set_cancellation_token(observer.token.clone());
+            assert!(matches!(
+                mdc.tessellate(),
+                Err(super::DualContouringError::Cancelled)
+            ));
+            assert!(observer
+                .progress
+                .lock()
+                .unwrap()
+                .iter()
+                .all(|&p| p < fraction + 0.2));
+        }
+    }
+
+    #[test]
+    fn config() -> Result<(), crate::mesh::MeshError> {
+        use crate::TessellationConfig;
+        let sphere = UnitSphere::new();
+        let default_mesh = super::ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate()
+            .unwrap();
+        let mut mdc = super::ManifoldDualContouring::with_config(
+            &sphere,
+            0.2,
+            0.1,
+            TessellationConfig::default(),
+        );
+        assert_eq!(mdc.tessellate().unwrap(), default_mesh);
+
+        let config = TessellationConfig::default().simplify(false);
+        let mut mdc = super::ManifoldDualContouring::with_config(&sphere, 0.2, 0.1, config);
+        let mesh = mdc.tessellate().unwrap();
+        assert!(mesh.faces.len() > default_mesh.faces.len());
+        mesh.is_closed()?;
+
+        let config = TessellationConfig::default()
+            .precision(0.001)
+            .qef_truncation(0.01)
+            .bbox_padding(0.)
+            .bbox_padding_cells(2.);
+        let mut mdc = super::ManifoldDualContouring::with_config(&sphere, 0.2, 0.0, config);
+        let mesh = mdc.tessellate().unwrap();
+        for v in &mesh.vertices {
+            let r = na::Vector3::new(v[0], v[1], v[2]).norm();
+            assert!((r - 1.).abs() < 0.05, "{:?} is not on the sphere", v);
+        }
+        mesh.is_closed()
+    }
+
+    #[test]
+    fn qef_options() -> Result<(), crate::mesh::MeshError> {
+        use crate::{QefClamp, TessellationConfig};
+        let cube = crate::sdf::RoundedBox::<f64>::new(na::Vector3::new(0.5, 0.5, 0.5), 0.0);
+        let corner_distance = |config: TessellationConfig<f64>| {
+            let mut mdc = super::ManifoldDualContouring::with_config(&cube, 0.15, 0.0, config);
+            let mesh = mdc.tessellate().unwrap();
+            let distance = mesh
+                .vertices
+                .iter()
+                .map(|v| (na::Vector3::from(*v) - na::Vector3::new(0.5, 0.5, 0.5)).norm())
+                .fold(f64::INFINITY, f64::min);
+            mesh.is_closed().map(|_| distance)
+        };
+        let sharp = corner_distance(TessellationConfig::default())?;
+        assert!(sharp < 1e-6, "{}", sharp);
+        let dilated =
+            corner_distance(TessellationConfig::default().qef_clamp(QefClamp::DilatedParent))?;
+        assert!(dilated < 1e-6, "{}", dilated);
+        // The regularization rounds off the corners.
+        let regularized = corner_distance(TessellationConfig::default().qef_regularization(1.0))?;
+        assert!(regularized > 0.01, "{}", regularized);
+        Ok(())
+    }
+
+    #[test]
+    fn root_finders() -> Result<(), crate::mesh::MeshError> {
+        use crate::{ImplicitFunction, RootFinder, TessellationConfig};
+        let torus = crate::sdf::Torus::<f64>::new(1.0, 0.3);
+        for method in [
+            RootFinder::Bisection,
+            RootFinder::Illinois,
+            RootFinder::Newton,
+        ] {
+            let config = TessellationConfig::default()
+                .root_finder(method)
+                .precision(0.0)
+                .absolute_precision(1e-6);
+            let mut mdc = super::ManifoldDualContouring::with_config(&torus, 0.1, 0.0, config);
+            mdc.tessellate().unwrap().is_closed()?;
+            for plane in mdc.edge_grid.values() {
+                assert!(torus.value(&plane.p).abs() < 1e-6, "{:?}", method);
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn region() -> Result<(), crate::mesh::MeshError> {
+        use crate::{Boundary, BoundingBox, TessellationConfig};
+        let sphere = UnitSphere::new();
+        // The half of the sphere with negative x. The region is not a multiple of the resolution
+        // wide, so the grid ends beyond it.
+        let region = BoundingBox::new(
+            &na::Point3::new(-2.1, -2., -2.),
+            &na::Point3::new(0., 2., 2.),
+        );
+
+        let config = TessellationConfig::default().region(region.clone());
+        let mut mdc = super::ManifoldDualContouring::with_config(&sphere, 0.2, 0.0, config);
+        let (mesh, stats) = mdc.tessellate_with_stats().unwrap();
+        assert_eq!(stats.boundary_loops, 1);
+        let boundary = &mesh.boundary_loops()[0];
+        assert!(boundary.len() > 10);
+        for &i in boundary {
+            let v = mesh.vertices[i];
+            assert!(v[0] > -0.4 && v[0] <= 0.0, "{:?} is not near the cut", v);
+        }
+        assert!(mesh.vertices.iter().all(|v| v[0] <= 0.0));
+
+        let config = TessellationConfig::default()
+            .region(region)
+            .boundary(Boundary::Capped);
+        let mut mdc = super::ManifoldDualContouring::with_config(&sphere, 0.2, 0.0, config);
+        let mesh = mdc.tessellate().unwrap();
+        assert!(mesh.vertices.iter().all(|v| v[0] <= 0.));
+        assert!(mesh.vertices.iter().filter(|v| v[0] == 0.).count() > 10);
+        mesh.is_closed()
+    }
+
+    #[test]
+    fn capped_lattices() -> Result<(), crate::mesh::MeshError> {
+        use crate::{sdf, Boundary, ImplicitFunction, TessellationConfig};
+        let gyroid = sdf::Gyroid::new(std::f64::consts::PI, 0.0, 1.3);
+        let schwartz_p = sdf::SchwartzP::new(std::f64::consts::PI, 0.0, 1.3);
+        for shape in [&gyroid as &dyn ImplicitFunction<f64>, &schwartz_p] {
+            let bbox = shape.bbox();
+            let config = TessellationConfig::default().boundary(Boundary::Capped);
+            let mut mdc = super::ManifoldDualContouring::with_config(shape, 0.1, 0.1, config);
+            let mesh = mdc.tessellate().unwrap();
+            mesh.is_closed()?;
+            let on_wall = |v: &[f64; 3]| (0..3).any(|i| v[i] == bbox.min[i] || v[i] == bbox.max[i]);
+            for v in &mesh.vertices {
+                assert!(
+                    bbox.contains(&na::Point3::from(*v)),
+                    "{:?} is outside of {:?}",
+                    v,
+                    bbox
+                );
+            }
+            // The sharp edges where the surface meets the walls are kept.
+            let rim = mesh
+                .vertices
+                .iter()
+                .filter(|v| {
+                    on_wall(v)
+                        && shape.value(&na::Point3::from(**v)).abs() < 0.01 * shape.lipschitz()
+                })
+                .count();
+            assert!(rim > 50, "only {} vertices on the rim", rim);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn mesh_sinks() {
+        use crate::{MeshSink, StlWriter};
+        use std::io;
+        let sphere = UnitSphere::new();
+        let mesh = super::ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate()
+            .unwrap();
+        let mut stl = StlWriter::new(io::Cursor::new(Vec::new())).unwrap();
+        let stats = super::ManifoldDualContouring::new(&sphere, 0.2, 0.1)
+            .tessellate_into(&mut stl)
+            .unwrap();
+        assert_eq!(stats.vertices, mesh.vertices.len());
+        assert_eq!(stats.faces, mesh.faces.len());
+        let stl = stl.into_inner().into_inner();
+        assert_eq!(stl.len(), 84 + 50 * mesh.faces.len());
+        assert_eq!(stl[80..84], (mesh.faces.len() as u32).to_le_bytes());
+
+        struct Full;
+        impl MeshSink<f64> for Full {
+            fn add_vertex(&mut self, _vertex: [f64; 3]) -> io::Result<()> {
+                Err(io::Error::other("disk full"))
+            }
+            fn add_face(&mut self, _face: [usize; 3]) -> io::Result<()> {
+                Ok(())
+            }
+        }
+        assert!(matches!(
+            super::ManifoldDualContouring::new(&sphere, 0.2, 0.1).tessellate_into(&mut Full),
+            Err(super::DualContouringError::Io(_))
+        ));
+    }
+
+    #[test]
+    fn levels_of_detail() -> Result<(), crate::mesh::MeshError> {
+        use crate::LevelOfDetail;
+        let sphere = UnitSphere::new();
+        let mut mdc = super::ManifoldDualContouring::new(&sphere, 0.1, 0.0);
+        let octree = mdc.build_octree().unwrap();
+        assert!(octree.depth() > 2);
+        for relative_error in [0.0, 0.1, 0.5] {
+            let expected = super::ManifoldDualContouring::new(&sphere, 0.1, relative_error)
+                .tessellate()
+                .unwrap();
+            assert_eq!(
+                octree.extract(LevelOfDetail::RelativeError(relative_error)),
+                expected
+            );
+        }
+        let finest = octree.extract(LevelOfDetail::Depth(0));
+        finest.is_closed()?;
+        let mut faces = finest.faces.len();
+        for depth in 1..octree.depth() {
+            let mesh = octree.extract(LevelOfDetail::Depth(depth));
+            assert!(mesh.faces.len() < faces, "{} {}", mesh.faces.len(), faces);
+            faces = mesh.faces.len();
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn face_budget() {
+        use crate::LevelOfDetail;
+        let sphere = UnitSphere::new();
+        let mut mdc = super::ManifoldDualContouring::new(&sphere, 0.1, 0.0);
+        let octree = mdc.build_octree().unwrap();
+        let finest = octree.extract(LevelOfDetail::RelativeError(0.0));
+        let coarsest = octree.extract(LevelOfDetail::RelativeError(f64::INFINITY));
+        for budget in [500, 1000, 2000] {
+            let faces = octree
+                .extract(LevelOfDetail::FaceBudget(budget))
+                .faces
+                .len();
+            assert!(
+                faces.abs_diff(budget) < budget / 20,
+                "{} faces for {}",
+                faces,
+                budget
+            );
+        }
+        assert_eq!(
+            octree.extract(LevelOfDetail::FaceBudget(usize::MAX)),
+            finest
+        );
+        assert_eq!(octree.extract(LevelOfDetail::FaceBudget(0)), coarsest);
+    }
+
+    #[test]
+    fn preserves_topology() -> Result<(), crate::mesh::MeshError> {
+        use crate::{sdf, topology::Topology, TessellationConfig};
+        struct Rejections(std::sync::atomic::AtomicUsize);
+        impl super::TessellationObserver for Rejections {
+            fn collapse_rejected(&self, level: usize, _cell: [usize; 3]) {
+                assert!(level > 0);
+                self.0.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            }
+        }
+        // Without manifold checks, merges across the thin tube of the torus pinch it.
+        let torus = sdf::Torus::new(1.0, 0.06);
+        for relative_error in [1.0, 10.0] {
+            let config = TessellationConfig::default()
+                .manifold_checks(false)
+                .preserve_topology(true);
+            let rejections = Rejections(Default::default());
+            let mut mdc =
+                super::ManifoldDualContouring::with_config(&torus, 0.05, relative_error, config);
+            mdc.set_observer(&rejections);
+            let (mesh, stats) = mdc.tessellate_with_stats().unwrap();
+            mesh.is_closed()?;
+            let topology = Topology::new(&mesh.faces);
+            assert!(topology.defects.is_empty());
+            assert_eq!(topology.components, vec![(0, 0)]);
+            assert!(stats.rejected_collapses > 0);
+            assert_eq!(
+                stats.rejected_collapses,
+                rejections.0.load(std::sync::atomic::Ordering::Relaxed)
+            );
+        }
+
+        // Cancellation stops the search for merges to reject before any is reported.
+        let config = TessellationConfig::default()
+            .manifold_checks(false)
+            .preserve_topology(true);
+        let rejections = Rejections(Default::default());
+        let token = crate::CancellationToken::new();
+        let mut mdc = super::ManifoldDualContouring::with_config(&torus, 0.05, 0.0, config);
+        mdc.set_observer(&rejections);
+        mdc.set_cancellation_token(token.clone());
+        let octree = mdc.build_octree().unwrap();
+        token.cancel();
+        assert!(matches!(
+            octree.extract_into(
+                crate::LevelOfDetail::RelativeError(10.0),
+                &mut crate::Mesh::default()
+            ),
+            Err(super::DualContouringError::Cancelled)
+        ));
+        assert_eq!(rejections.0.load(std::sync::atomic::Ordering::Relaxed), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn preserves_topology_of_open_meshes() {
+        use crate::{sdf, topology::Topology, BoundingBox, TessellationConfig};
+        // Cut through both sides of the ring, the torus becomes a tube with two boundary loops.
+        let torus = sdf::Torus::new(1.0, 0.06);
+        let region = BoundingBox::new(
+            &na::Point3::new(-2., -2., -2.),
+            &na::Point3::new(0.5, 2., 2.),
+        );
+        let config = TessellationConfig::default()
+            .region(region)
+            .manifold_checks(false)
+            .preserve_topology(true);
+        let mut mdc = super::ManifoldDualContouring::with_config(&torus, 0.05, 10.0, config);
+        let (mesh, stats) = mdc.tessellate_with_stats().unwrap();
+        let topology = Topology::new(&mesh.faces);
+        assert!(topology.defects.is_empty());
+        assert_eq!(topology.components, vec![(0, 2)]);
+        assert_eq!(stats.boundary_loops, 2);
+        assert!(stats.rejected_collapses > 0);
+    }
+
+    #[test]
+    fn face_budget_preserves_topology() -> Result<(), crate::mesh::MeshError> {
+        use crate::{sdf, topology::Topology, LevelOfDetail, TessellationConfig};
+        let torus = sdf::Torus::new(1.0, 0.06);
+        let config = TessellationConfig::default()
+            .manifold_checks(false)
+            .preserve_topology(true);
+        let mut mdc = super::ManifoldDualContouring::with_config(&torus, 0.05, 0.0, config);
+        let octree = mdc.build_octree().unwrap();
+        // The budget is met with the merges that topology preservation keeps.
+        for budget in [1000, 2000] {
+            let mesh = octree.extract(LevelOfDetail::FaceBudget(budget));
+            mesh.is_closed()?;
+            assert!(
+                mesh.faces.len().abs_diff(budget) < budget / 20,
+                "{} faces for {}",
+                mesh.faces.len(),
+                budget
+            );
+            let topology = Topology::new(&mesh.faces);
+            assert!(topology.defects.is_empty());
+            assert_eq!(topology.components, vec![(0, 0)]);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn error_field() {
+        use crate::{BoundingBox, ErrorRegions};
+        let sphere = UnitSphere::new();
+        let tessellate = |relative_error: f64, field: Option<&dyn crate::ErrorField<f64>>| {
+            let mut mdc = super::ManifoldDualContouring::new(&sphere, 0.1, relative_error);
+            if let Some(field) = field {
+                mdc.set_error_field(field);
+            }
+            mdc.tessellate().unwrap()
+        };
+        let fine = tessellate(0.0, None);
+        let coarse = tessellate(0.5, None);
+        // A field that covers everything with the same error matches the constant error.
+        let everywhere = ErrorRegions::new(0.0).add(
+            BoundingBox::new(
+                &na::Point3::new(-5., -5., -5.),
+                &na::Point3::new(5., 5., 5.),
+            ),
+            0.5,
+        );
+        assert_eq!(tessellate(0.0, Some(&everywhere)), coarse);
+        // Keeping one half detailed gives a mesh in between.
+        let half = |p: &na::Point3<f64>| if p.x < 0. { 0.0 } else { 0.5 };
+        let mixed = tessellate(0.5, Some(&half));
+        assert!(mixed.faces.len() < fine.faces.len());
+        assert!(mixed.faces.len() > coarse.faces.len());
+        let left_faces = |mesh: &crate::Mesh<f64>| {
+            (0..mesh.faces.len())
+                .filter(|&f| mesh.faces[f].iter().all(|&v| mesh.vertices[v][0] < -0.5))
+                .count()
+        };
+        assert!(left_faces(&mixed) > left_faces(&coarse));
+    }
+
     #[test]
     fn sdf_sphere_with_simplification() {
         use crate::sdf;
@@ -1097,7 +3252,7 @@ mod tests {
     #[test]
     fn sdf_gyroid() {
         use crate::sdf;
-        let shape = sdf::Gyroid::new(3.14, 0.0, 2.0);
+        let shape = sdf::Gyroid::new(std::f64::consts::PI, 0.0, 2.0);
         let mut mdc = super::ManifoldDualContouring::new(&shape, 0.1, 0.1);
         let mesh = mdc.tessellate().unwrap();
         assert!(!mesh.vertices.is_empty());
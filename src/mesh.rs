@@ -27,6 +27,55 @@ pub struct Mesh<S> {
     pub faces: Vec<[usize; 3]>,
 }
 
+impl<S> Default for Mesh<S> {
+    fn default() -> Self {
+        Mesh {
+            vertices: Vec::new(),
+            faces: Vec::new(),
+        }
+    }
+}
+
+impl<S> Mesh<S> {
+    /// Return the holes of the mesh. Each hole is returned as loop of vertex indexes, ordered
+    /// like the edges of the faces that border it. A closed mesh has no boundary loops.
+    pub fn boundary_loops(&self) -> Vec<Vec<usize>> {
+        let mut edges = std::collections::HashSet::new();
+        for face in &self.faces {
+            for i in 0..3 {
+                edges.insert((face[i], face[(i + 1) % 3]));
+            }
+        }
+        let mut next = std::collections::BTreeMap::new();
+        for &(a, b) in &edges {
+            if !edges.contains(&(b, a)) {
+                next.entry(a).or_insert_with(Vec::new).push(b);
+            }
+        }
+        for targets in next.values_mut() {
+            targets.sort_unstable_by(|a, b| b.cmp(a));
+        }
+        let mut result = Vec::new();
+        while let Some(&start) = next.keys().next() {
+            let mut boundary = vec![start];
+            let mut current = start;
+            while let Some(targets) = next.get_mut(&current) {
+                let target = targets.pop().unwrap();
+                if targets.is_empty() {
+                    next.remove(&current);
+                }
+                if target == start {
+                    break;
+                }
+                boundary.push(target);
+                current = target;
+            }
+            result.push(boundary);
+        }
+        result
+    }
+}
+
 impl<S: RealField + Copy + Debug> Mesh<S> {
     /// Return the normal of the face at index face as triple of f32.
     pub fn normal32(&self, face: usize) -> [f32; 3]
@@ -122,4 +171,20 @@ mod test {
         assert!(f32slice_eq(&m.vertex32(1), &[1., 0., 0.]));
         assert!(f32slice_eq(&m.vertex32(2), &[0., 1., 0.]));
     }
+
+    #[test]
+    fn boundary_loops() {
+        // Two triangles forming a square.
+        let m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [1., 1., 0.], [0., 1., 0.]],
+            faces: vec![[0, 1, 2], [2, 3, 0]],
+        };
+        assert_eq!(m.boundary_loops(), vec![vec![0, 1, 2, 3]]);
+        // A closed tetrahedron.
+        let m = Mesh {
+            vertices: vec![[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]],
+            faces: vec![[0, 2, 1], [0, 1, 3], [1, 2, 3], [2, 0, 3]],
+        };
+        assert!(m.boundary_loops().is_empty());
+    }
 }
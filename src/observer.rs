@@ -0,0 +1,85 @@
+use crate::DualContouringError;
+use std::time::Duration;
+
+/// The stages of the tessellation pipeline in the order in which they run.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Stage {
+    /// Adaptive sampling of the function on the value grid.
+    SampleValueGrid,
+    /// Removal of grid points that are not adjacent to a sign change.
+    CompactValueGrid,
+    /// Search for the surface crossing on every grid edge with a sign change.
+    EdgeGrid,
+    /// Creation of one vertex per surface patch and cell.
+    LeafVertices,
+    /// Clustering of vertices into the coarser levels of the octree.
+    SubsampleOcttree,
+    /// Minimization of the quadratic error functions.
+    SolveQefs,
+    /// Generation of the faces.
+    GenerateQuads,
+}
+
+/// Reported to a [`TessellationObserver`] whenever a stage finished.
+#[derive(Clone, Debug, PartialEq)]
+pub struct StageEvent {
+    /// The stage that finished.
+    pub stage: Stage,
+    /// Number of items the stage produced: grid points for sampling and compaction, edges for
+    /// the edge grid, vertices for the leaf vertices, octree levels for subsampling, solved
+    /// QEFs and faces for quad generation.
+    pub count: usize,
+    /// Wall time spent in the stage. Always zero on wasm32, which has no clock.
+    pub duration: Duration,
+}
+
+/// Receives progress information from [`ManifoldDualContouring`](crate::ManifoldDualContouring).
+///
+/// All methods default to doing nothing. Observers must be `Sync`, since parts of the pipeline run
+/// on several threads.
+pub trait TessellationObserver: Sync {
+    /// Called after each stage of the pipeline.
+    fn stage_finished(&self, _event: &StageEvent) {}
+    /// Called with the estimated fraction of work done, between 0 and 1. The fraction starts
+    /// over after a retry.
+    fn progress(&self, _fraction: f32) {}
+    /// Called when the function evaluated to zero on a grid point and tessellation restarts
+    /// with a shifted grid, which only happens with
+    /// [`ZeroHandling::Restart`](crate::ZeroHandling::Restart).
+    fn retry(&self, _error: &DualContouringError) {}
+    /// Called for every merge that
+    /// [`preserve_topology`](crate::TessellationConfig::preserve_topology) rejected, with the
+    /// octree level of the merged cell and its index on that level. Cells of level `l` span
+    /// `2^l` grid cells along each axis.
+    fn collapse_rejected(&self, _level: usize, _cell: [usize; 3]) {}
+}
+
+/// Statistics of a finished tessellation.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TessellationStats {
+    /// One event per stage of the successful attempt, in execution order.
+    pub stages: Vec<StageEvent>,
+    /// Number of restarts after hitting a zero value.
+    pub retries: usize,
+    /// Number of vertices in the mesh.
+    pub vertices: usize,
+    /// Number of faces in the mesh.
+    pub faces: usize,
+    /// Number of holes where the surface was cut open at the sampling region. Only counted for
+    /// [`Boundary::Open`](crate::Boundary::Open) with an explicit region, zero otherwise.
+    pub boundary_loops: usize,
+    /// Number of merges rejected because they changed the topology of the mesh, with
+    /// [`preserve_topology`](crate::TessellationConfig::preserve_topology).
+    pub rejected_collapses: usize,
+}
+
+impl TessellationStats {
+    /// Return the event for the given stage, if it ran.
+    pub fn stage(&self, stage: Stage) -> Option<&StageEvent> {
+        self.stages.iter().find(|e| e.stage == stage)
+    }
+    /// Return the sum of all stage durations.
+    pub fn duration(&self) -> Duration {
+        self.stages.iter().map(|e| e.duration).sum()
+    }
+}
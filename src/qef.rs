@@ -4,7 +4,9 @@ use nalgebra::RealField;
 use num_traits::Float;
 use std::{convert, fmt::Debug};
 
-pub const EPSILON: f32 = 1e-10;
+// Distance relative to the size of the bbox up to which solutions outside are considered inside,
+// since they only miss it due to rounding errors.
+const ROUNDING_TOLERANCE: f32 = 1e-6;
 
 /// Quadratic error function
 #[derive(Clone, Debug)]
@@ -57,64 +59,36 @@ impl<S: RealField + Float + Debug + From<f32>> Qef<S> {
         }
         qef
     }
-    pub fn solve(&mut self) {
+    /// Minimize the QEF within its bbox, grown by `dilation` on all sides.
+    ///
+    /// Directions whose singular value is below `truncation` times the largest one are
+    /// ignored, so that the solution stays at the mass point along them instead of following
+    /// noise in nearly parallel planes. `regularization` adds a penalty on the squared distance
+    /// to the mass point with this weight per plane, which pulls the solution toward it in all
+    /// directions. If the minimum is outside the bbox, the constrained minimum on the walls,
+    /// edges and corners of the bbox is returned instead.
+    pub fn solve(&mut self, truncation: S, regularization: S, dilation: S) {
         let m = &self.ata;
         let ma = na::Matrix3::new(m[0], m[1], m[2], m[1], m[3], m[4], m[2], m[4], m[5]);
         let sum_as_s: S = convert::From::from(self.num as f32);
         let mean: na::Vector3<S> = self.sum / sum_as_s;
-        if let Some(inv) = ma.try_inverse() {
-            let b_rel_mean: na::Vector3<S> = self.atb - ma * mean;
-            self.solution = inv * b_rel_mean + mean;
-        }
-
-        if !self.bbox.contains(&na::Point3::new(
-            self.solution.x,
-            self.solution.y,
-            self.solution.z,
-        )) {
-            let accuracy = (self.bbox.max.x - self.bbox.min.x) / convert::From::from(100.0);
-            self.solution = self.search_solution(accuracy, &mut self.bbox.clone(), &ma);
-            debug_assert!(
-                self.bbox.dilate(accuracy).contains(&na::Point3::new(
-                    self.solution.x,
-                    self.solution.y,
-                    self.solution.z
-                )),
-                "{:?} outside of {:?}",
-                self.solution,
-                self
-            );
+        let weight = regularization * sum_as_s;
+        let system = System {
+            ma: ma + na::Matrix3::identity() * weight,
+            atb: self.atb + mean * weight,
+            mean,
+            truncation,
+        };
+        let bounds = self.bbox.dilate(dilation);
+        self.solution = system.minimize(mean, na::Matrix3::identity());
+        if !contains(&bounds, &self.solution) {
+            // Candidates are compared by the objective they minimize, including the penalty.
+            let objective =
+                |x: &na::Vector3<S>| self.error(x, &ma) + (x - mean).norm_squared() * weight;
+            self.solution = system.constrained_minimum(&bounds, objective);
         }
         self.error = self.error(&self.solution, &ma);
     }
-    fn search_solution(
-        &self,
-        accuracy: S,
-        bbox: &mut BoundingBox<S>,
-        ma: &na::Matrix3<S>,
-    ) -> na::Vector3<S> {
-        let mid = na::Point3::new(
-            (bbox.max.x + bbox.min.x) * convert::From::from(0.5),
-            (bbox.max.y + bbox.min.y) * convert::From::from(0.5),
-            (bbox.max.z + bbox.min.z) * convert::From::from(0.5),
-        );
-        let na_mid = na::Vector3::new(mid.x, mid.y, mid.z);
-        if bbox.max.x - bbox.min.x <= accuracy {
-            return na_mid;
-        }
-        let mid_error = self.error(&na_mid, ma);
-        for dim in 0..3 {
-            let mut d_mid = na_mid;
-            d_mid[dim] += convert::From::from(EPSILON);
-            let d_error = self.error(&d_mid, ma);
-            if d_error < mid_error {
-                bbox.min[dim] = mid[dim];
-            } else {
-                bbox.max[dim] = mid[dim];
-            }
-        }
-        self.search_solution(accuracy, bbox, ma)
-    }
     fn error(&self, point: &na::Vector3<S>, ma: &na::Matrix3<S>) -> S {
         let _2_as_s: S = convert::From::from(2f32);
         self.btb - _2_as_s * na::Matrix::dot(point, &self.atb)
@@ -132,6 +106,90 @@ impl<S: RealField + Float + Debug + From<f32>> Qef<S> {
     }
 }
 
+// The normal equations of a QEF, possibly regularized toward the mass point.
+struct System<S: RealField> {
+    ma: na::Matrix3<S>,
+    atb: na::Vector3<S>,
+    mean: na::Vector3<S>,
+    truncation: S,
+}
+
+impl<S: RealField + Float + Debug + From<f32>> System<S> {
+    // Minimizes over the coordinates selected by the diagonal projection `free`, starting from
+    // `start`, whose other coordinates stay fixed.
+    fn minimize(&self, start: na::Vector3<S>, free: na::Matrix3<S>) -> na::Vector3<S> {
+        let reduced = free * self.ma * free;
+        let residual = free * (self.atb - self.ma * start);
+        start + pseudo_inverse(&reduced, self.truncation) * residual
+    }
+    // Returns the point with the smallest objective among the local minima on all walls, edges
+    // and corners of bounds. Since the QEF is convex, the minimum within bounds is one of them.
+    fn constrained_minimum(
+        &self,
+        bounds: &BoundingBox<S>,
+        objective: impl Fn(&na::Vector3<S>) -> S,
+    ) -> na::Vector3<S> {
+        let mut best = None;
+        let mut best_value = S::infinity();
+        // Each coordinate is either free, at the lower or at the upper end of the bounds. Skip
+        // the first combination, where all are free.
+        for combination in 1..27 {
+            let mut start = self.mean;
+            let mut free = na::Matrix3::zeros();
+            for (i, choice) in [combination % 3, combination / 3 % 3, combination / 9]
+                .into_iter()
+                .enumerate()
+            {
+                match choice {
+                    0 => free[(i, i)] = S::one(),
+                    1 => start[i] = bounds.min[i],
+                    _ => start[i] = bounds.max[i],
+                }
+            }
+            let candidate = self.minimize(start, free);
+            if !contains(bounds, &candidate) {
+                continue;
+            }
+            let value = objective(&candidate);
+            if best.is_none() || value < best_value {
+                best = Some(candidate);
+                best_value = value;
+            }
+        }
+        // The corners are always within bounds, so there is a candidate unless the QEF is NaN.
+        let best = best.unwrap_or(self.mean);
+        // Remove rounding errors of the free coordinates.
+        na::Vector3::from_fn(|i, _| Float::min(Float::max(best[i], bounds.min[i]), bounds.max[i]))
+    }
+}
+
+// Returns true if the point is within bounds, up to rounding errors.
+fn contains<S: RealField + Float + From<f32>>(
+    bounds: &BoundingBox<S>,
+    point: &na::Vector3<S>,
+) -> bool {
+    let size = bounds.max - bounds.min;
+    let tolerance = size.amax() * convert::From::from(ROUNDING_TOLERANCE);
+    bounds.dilate(tolerance).contains(&na::Point3::from(*point))
+}
+
+// Returns the pseudo-inverse of the symmetric positive semi-definite matrix m, ignoring
+// eigenvalues below truncation^2 times the largest one. Those are the squared singular values of
+// the plane normals, hence the square.
+fn pseudo_inverse<S: RealField + Float>(m: &na::Matrix3<S>, truncation: S) -> na::Matrix3<S> {
+    let eigen = na::SymmetricEigen::new(*m);
+    let largest = eigen.eigenvalues.amax();
+    let mut result = na::Matrix3::zeros();
+    for i in 0..3 {
+        let value = eigen.eigenvalues[i];
+        if value > S::zero() && value >= largest * truncation * truncation {
+            let v = eigen.eigenvectors.column(i);
+            result += v * v.transpose() / value;
+        }
+    }
+    result
+}
+
 #[cfg(test)]
 mod tests {
     use super::Plane;
@@ -158,7 +216,7 @@ mod tests {
             ],
             BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 1., 1.)),
         );
-        qef.solve();
+        qef.solve(0.1, 0., 0.);
         assert!(
             qef.solution.norm() < 0.01,
             "{:?} nowhere near origin",
@@ -185,8 +243,8 @@ mod tests {
             ],
             BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 1., 1.)),
         );
-        qef.solve();
-        assert!(relative_eq!(qef.solution, &na::Vector3::new(0., 0., 0.)));
+        qef.solve(0.1, 0., 0.);
+        assert_relative_eq!(qef.solution, na::Vector3::new(0., 0., 0.), epsilon = 1e-12);
     }
 
     #[test]
@@ -208,7 +266,7 @@ mod tests {
             ],
             BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 2., 3.)),
         );
-        qef.solve();
+        qef.solve(0.1, 0., 0.);
         let expected_solution = na::Vector3::new(1., 2., 3.);
         assert!(
             relative_eq!(qef.solution, &expected_solution),
@@ -217,4 +275,99 @@ mod tests {
             expected_solution
         );
     }
+
+    #[test]
+    fn nearly_flat_solution_at_mass_point() {
+        // Slightly tilted planes through points of the plane z = 0.5. The mass point is only
+        // moved onto the plane instead of following the tilts.
+        let planes: Vec<_> = [(0.2, 0.3, 0.01), (0.7, 0.4, -0.01), (0.5, 0.8, 0.005)]
+            .iter()
+            .map(|&(x, y, tilt)| Plane {
+                p: na::Point3::new(x, y, 0.5),
+                n: na::Vector3::new(tilt, -tilt, 1.).normalize(),
+            })
+            .collect();
+        let mut qef = Qef::new(
+            &planes,
+            BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 1., 1.)),
+        );
+        qef.solve(0.1, 0., 0.);
+        assert_relative_eq!(
+            qef.solution,
+            na::Vector3::new(14. / 30., 0.5, 0.5),
+            epsilon = 1e-2
+        );
+    }
+
+    #[test]
+    fn exact_constrained_solution() {
+        // The unconstrained minimum at x = 2 lies outside, so the solution moves onto the wall
+        // x = 1 without snapping to the nearest corner or the center of the wall.
+        let mut qef = Qef::new(
+            &[
+                Plane {
+                    p: na::Point3::new(2., 0.3, 0.6),
+                    n: na::Vector3::new(1., 0., 0.),
+                },
+                Plane {
+                    p: na::Point3::new(0.5, 0.3, 0.5),
+                    n: na::Vector3::new(0., 1., 0.),
+                },
+            ],
+            BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(1., 1., 1.)),
+        );
+        qef.solve(0.1, 0., 0.);
+        assert_relative_eq!(
+            qef.solution,
+            na::Vector3::new(1., 0.3, 0.55),
+            epsilon = 1e-12
+        );
+    }
+
+    #[test]
+    fn regularization_and_dilation() {
+        // Three planes meet at (1, 1, 1), while the mass point is at (1/3, 1/3, 1/3).
+        let planes = [
+            Plane {
+                p: na::Point3::new(1., 0., 0.),
+                n: na::Vector3::new(1., 0., 0.),
+            },
+            Plane {
+                p: na::Point3::new(0., 1., 0.),
+                n: na::Vector3::new(0., 1., 0.),
+            },
+            Plane {
+                p: na::Point3::new(0., 0., 1.),
+                n: na::Vector3::new(0., 0., 1.),
+            },
+        ];
+        let bbox =
+            BoundingBox::<f64>::new(&na::Point3::new(0., 0., 0.), &na::Point3::new(2., 2., 2.));
+        let mut qef = Qef::new(&planes, bbox);
+        qef.solve(0.1, 0., 0.);
+        assert_relative_eq!(qef.solution, na::Vector3::new(1., 1., 1.), epsilon = 1e-12);
+        // Minimizes (x - 1)^2 + 3 (x - 1/3)^2 along each axis.
+        qef.solve(0.1, 1., 0.);
+        assert_relative_eq!(
+            qef.solution,
+            na::Vector3::new(0.5, 0.5, 0.5),
+            epsilon = 1e-12
+        );
+        assert_relative_eq!(qef.error, 0.75, epsilon = 1e-12);
+
+        // Within a smaller box, the corner can only be reached if the box is dilated.
+        let bbox = BoundingBox::<f64>::new(
+            &na::Point3::new(0., 0., 0.),
+            &na::Point3::new(0.8, 0.8, 0.8),
+        );
+        let mut qef = Qef::new(&planes, bbox);
+        qef.solve(0.1, 0., 0.);
+        assert_relative_eq!(
+            qef.solution,
+            na::Vector3::new(0.8, 0.8, 0.8),
+            epsilon = 1e-12
+        );
+        qef.solve(0.1, 0., 0.4);
+        assert_relative_eq!(qef.solution, na::Vector3::new(1., 1., 1.), epsilon = 1e-12);
+    }
 }
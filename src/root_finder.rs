@@ -0,0 +1,247 @@
+use crate::config::RootFinder;
+use nalgebra as na;
+use nalgebra::RealField;
+
+// Searches the crossing of the surface between a and b, whose values av and bv differ in sign.
+//
+// The search runs on the parameter t of the segment a + t * (b - a) and keeps the bracket
+// [t0, t1] around the crossing. It stops once the bracket is shorter than tolerance, once the
+// value at one of its ends is smaller, or after max_iterations evaluations, and returns the best
+// estimate of the crossing at that point.
+#[allow(clippy::too_many_arguments)]
+pub fn find_root<S, V, G>(
+    method: RootFinder,
+    a: na::Point3<S>,
+    av: S,
+    b: na::Point3<S>,
+    bv: S,
+    tolerance: S,
+    max_iterations: usize,
+    value: V,
+    value_and_gradient: G,
+) -> na::Point3<S>
+where
+    S: RealField + Copy,
+    V: Fn(&na::Point3<S>) -> S,
+    G: Fn(&na::Point3<S>) -> (S, na::Vector3<S>),
+{
+    let edge = b - a;
+    let mut bracket = Bracket {
+        t0: S::zero(),
+        v0: av,
+        t1: S::one(),
+        v1: bv,
+        length: edge.norm(),
+        tolerance,
+    };
+    let point = |t: S| a + edge * t;
+    if bracket.converged() {
+        // Interpolate instead of returning the closer end point, so that crossings with
+        // linear functions such as the walls of a capped region are exact.
+        return point(bracket.interpolate());
+    }
+    match method {
+        RootFinder::Bisection => {
+            let two = S::one() + S::one();
+            for _ in 0..max_iterations {
+                let t = (bracket.t0 + bracket.t1) / two;
+                let v = value(&point(t));
+                if v.is_zero() {
+                    return point(t);
+                }
+                bracket.update(t, v);
+                if bracket.converged() {
+                    break;
+                }
+            }
+            point(bracket.interpolate())
+        }
+        RootFinder::Illinois => {
+            // Values used for interpolation, which are scaled down at an end that stays fixed.
+            let (mut w0, mut w1) = (bracket.v0, bracket.v1);
+            // Whether the lower end moved in the last step, if there was one.
+            let mut lower_moved = None;
+            for _ in 0..max_iterations {
+                let t = bracket.t0 + (bracket.t1 - bracket.t0) * w0 / (w0 - w1);
+                let v = value(&point(t));
+                if v.is_zero() {
+                    return point(t);
+                }
+                if bracket.update(t, v) {
+                    // The lower end moved, so the upper end was kept.
+                    if lower_moved == Some(true) {
+                        w1 *= anderson_bjorck(v, w0);
+                    }
+                    w0 = v;
+                    lower_moved = Some(true);
+                } else {
+                    if lower_moved == Some(false) {
+                        w0 *= anderson_bjorck(v, w1);
+                    }
+                    w1 = v;
+                    lower_moved = Some(false);
+                }
+                if bracket.converged() {
+                    break;
+                }
+            }
+            point(bracket.interpolate())
+        }
+        RootFinder::Newton => {
+            let two = S::one() + S::one();
+            let mut t = bracket.interpolate();
+            for _ in 0..max_iterations {
+                let (v, gradient) = value_and_gradient(&point(t));
+                if v.is_zero() {
+                    break;
+                }
+                bracket.update(t, v);
+                let step = v / gradient.dot(&edge);
+                let next = t - step;
+                // Bisect if the step leaves the bracket or is not finite.
+                let inside = next > bracket.t0 && next < bracket.t1;
+                let next = if inside {
+                    next
+                } else {
+                    (bracket.t0 + bracket.t1) / two
+                };
+                let small_step = (next - t).abs() * bracket.length < tolerance;
+                t = next;
+                if small_step || v.abs() < tolerance || bracket.converged() {
+                    break;
+                }
+            }
+            point(t)
+        }
+    }
+}
+
+// The factor by which false position scales the value at the end that stays fixed, given the new
+// value v at the moving end and its previous value.
+fn anderson_bjorck<S: RealField + Copy>(v: S, previous: S) -> S {
+    let factor = S::one() - v / previous;
+    if factor > S::zero() {
+        factor
+    } else {
+        S::one() / (S::one() + S::one())
+    }
+}
+
+struct Bracket<S> {
+    t0: S,
+    v0: S,
+    t1: S,
+    v1: S,
+    // Length of the segment, to convert distances on t to absolute ones.
+    length: S,
+    tolerance: S,
+}
+
+impl<S: RealField + Copy> Bracket<S> {
+    fn converged(&self) -> bool {
+        (self.t1 - self.t0) * self.length < self.tolerance
+            || self.v0.abs().min(self.v1.abs()) < self.tolerance
+    }
+    // Where the line through both ends crosses zero.
+    fn interpolate(&self) -> S {
+        self.t0 + (self.t1 - self.t0) * self.v0 / (self.v0 - self.v1)
+    }
+    // Replaces the end on the same side of the surface as v, where zero counts as outside.
+    // Returns true if that was the lower end.
+    fn update(&mut self, t: S, v: S) -> bool {
+        if (v < S::zero()) == (self.v0 < S::zero()) {
+            self.t0 = t;
+            self.v0 = v;
+            true
+        } else {
+            self.t1 = t;
+            self.v1 = v;
+            false
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::find_root;
+    use crate::config::RootFinder;
+    use nalgebra as na;
+    use std::cell::Cell;
+
+    // Finds the root of f(x) = x^3 + x - 1 between x = 0 and x = 1, and returns it together
+    // with the number of evaluations.
+    fn solve(method: RootFinder, tolerance: f64, max_iterations: usize) -> (f64, usize) {
+        let evaluations = Cell::new(0);
+        let f = |p: &na::Point3<f64>| {
+            evaluations.set(evaluations.get() + 1);
+            p.x * p.x * p.x + p.x - 1.
+        };
+        let root = find_root(
+            method,
+            na::Point3::new(0., 0., 0.),
+            -1.,
+            na::Point3::new(1., 0., 0.),
+            1.,
+            tolerance,
+            max_iterations,
+            f,
+            |p| (f(p), na::Vector3::new(3. * p.x * p.x + 1., 0., 0.)),
+        );
+        assert_eq!((root.y, root.z), (0., 0.));
+        (root.x, evaluations.get())
+    }
+
+    const ROOT: f64 = 0.6823278038280193;
+
+    #[test]
+    fn all_methods_converge() {
+        for method in [
+            RootFinder::Bisection,
+            RootFinder::Illinois,
+            RootFinder::Newton,
+        ] {
+            let (root, _) = solve(method, 1e-10, 100);
+            assert_relative_eq!(root, ROOT, epsilon = 1e-9);
+        }
+    }
+
+    #[test]
+    fn faster_methods_need_fewer_evaluations() {
+        let (_, bisection) = solve(RootFinder::Bisection, 1e-10, 100);
+        let (_, illinois) = solve(RootFinder::Illinois, 1e-10, 100);
+        let (_, newton) = solve(RootFinder::Newton, 1e-10, 100);
+        assert!(newton < illinois, "{} {}", newton, illinois);
+        assert!(illinois < bisection, "{} {}", illinois, bisection);
+    }
+
+    #[test]
+    fn limits_iterations() {
+        for method in [
+            RootFinder::Bisection,
+            RootFinder::Illinois,
+            RootFinder::Newton,
+        ] {
+            let (root, evaluations) = solve(method, 1e-10, 3);
+            assert_eq!(evaluations, 3);
+            assert!(root > 0. && root < 1.);
+        }
+    }
+
+    #[test]
+    fn newton_falls_back_to_bisection() {
+        // A gradient pointing the wrong way sends all Newton steps out of the bracket.
+        let f = |p: &na::Point3<f64>| p.x * p.x - 0.09;
+        let root = find_root(
+            RootFinder::Newton,
+            na::Point3::new(0., 0., 0.),
+            -0.09,
+            na::Point3::new(1., 0., 0.),
+            0.91,
+            1e-8,
+            100,
+            f,
+            |p| (f(p), na::Vector3::new(-1., 0., 0.)),
+        );
+        assert_relative_eq!(root.x, 0.3, epsilon = 1e-6);
+    }
+}
@@ -13,8 +13,9 @@
 //! let mesh = mdc.tessellate().unwrap();
 //! ```
 
-use crate::{BoundingBox, ImplicitFunction};
+use crate::{is_unbounded, BoundingBox, Dual, ImplicitFunction};
 use nalgebra as na;
+use std::cmp::Ordering;
 use std::fmt::Debug;
 
 // ---------------------------------------------------------------------------
@@ -48,8 +49,12 @@ impl<S: na::RealField + Copy + Debug + From<f32>> ImplicitFunction<S> for Sphere
     fn value(&self, p: &na::Point3<S>) -> S {
         na::Vector3::new(p.x, p.y, p.z).norm() - self.radius
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
-        na::Vector3::new(p.x, p.y, p.z).normalize()
+    fn value_and_gradient(&self, p: &na::Point3<S>, _epsilon: S) -> (S, na::Vector3<S>) {
+        let length = p.coords.norm();
+        (length - self.radius, p.coords / length)
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        shift(norm(&axes(bbox)), -self.radius)
     }
 }
 
@@ -92,8 +97,13 @@ impl<S: na::RealField + Copy + Debug + From<f32>> ImplicitFunction<S> for Rounde
         let inside = q.x.max(q.y.max(q.z)).min(zero);
         outside + inside - self.radius
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
-        finite_difference_normal(self, p)
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let zero = (S::zero(), S::zero());
+        let axes = axes(bbox);
+        let q: [(S, S); 3] = std::array::from_fn(|i| shift(abs(axes[i]), -self.half_extents[i]));
+        let outside = norm(&q.map(|q| max(q, zero)));
+        let inside = min(max(max(q[0], q[1]), q[2]), zero);
+        shift(add(outside, inside), -self.radius)
     }
 }
 
@@ -130,23 +140,25 @@ impl<S: na::RealField + Copy + Debug + From<f32>> ImplicitFunction<S> for Torus<
         let q_x = xz_len - self.major_radius;
         (q_x * q_x + p.y * p.y).sqrt() - self.minor_radius
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+    fn value_and_gradient(&self, p: &na::Point3<S>, _epsilon: S) -> (S, na::Vector3<S>) {
         let xz_len = (p.x * p.x + p.z * p.z).sqrt();
-        let zero: S = From::from(0f32);
+        let q_x = xz_len - self.major_radius;
+        let length = (q_x * q_x + p.y * p.y).sqrt();
+        let value = length - self.minor_radius;
         if xz_len < From::from(1e-10f32) {
-            return na::Vector3::new(
-                zero,
-                if p.y >= zero {
-                    From::from(1f32)
-                } else {
-                    From::from(-1f32)
-                },
-                zero,
-            );
+            // On the axis all points of the center circle are equally close.
+            return (value, na::Vector3::new(S::zero(), p.y / length, S::zero()));
         }
-        let center_x = p.x * self.major_radius / xz_len;
-        let center_z = p.z * self.major_radius / xz_len;
-        na::Vector3::new(p.x - center_x, p.y, p.z - center_z).normalize()
+        let radial = q_x / (xz_len * length);
+        (
+            value,
+            na::Vector3::new(p.x * radial, p.y / length, p.z * radial),
+        )
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let [x, y, z] = axes(bbox);
+        let q_x = shift(norm(&[x, z]), -self.major_radius);
+        shift(norm(&[q_x, y]), -self.minor_radius)
     }
 }
 
@@ -185,8 +197,14 @@ impl<S: na::RealField + Copy + Debug + From<f32>> ImplicitFunction<S> for Cylind
         let inside = d_radial.max(d_height).min(zero);
         outside + inside
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
-        finite_difference_normal(self, p)
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let zero = (S::zero(), S::zero());
+        let [x, y, z] = axes(bbox);
+        let d_radial = shift(norm(&[x, z]), -self.radius);
+        let d_height = shift(abs(y), -self.half_height);
+        let outside = norm(&[max(d_radial, zero), max(d_height, zero)]);
+        let inside = min(max(d_radial, d_height), zero);
+        add(outside, inside)
     }
 }
 
@@ -226,7 +244,7 @@ impl<S: na::RealField + Copy + Debug + From<f32>> ImplicitFunction<S> for Gyroid
         let cz = (p.z * self.scale).cos();
         sx * cy + sy * cz + sz * cx - self.threshold
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+    fn value_and_gradient(&self, p: &na::Point3<S>, _epsilon: S) -> (S, na::Vector3<S>) {
         let s = self.scale;
         let sx = (p.x * s).sin();
         let cx = (p.x * s).cos();
@@ -234,12 +252,26 @@ impl<S: na::RealField + Copy + Debug + From<f32>> ImplicitFunction<S> for Gyroid
         let cy = (p.y * s).cos();
         let sz = (p.z * s).sin();
         let cz = (p.z * s).cos();
-        na::Vector3::new(
-            s * (cx * cy - sz * sx),
-            s * (-sx * sy + cy * cz),
-            s * (-sy * sz + cz * cx),
+        (
+            sx * cy + sy * cz + sz * cx - self.threshold,
+            na::Vector3::new(
+                s * (cx * cy - sz * sx),
+                s * (-sx * sy + cy * cz),
+                s * (-sy * sz + cz * cx),
+            ),
         )
-        .normalize()
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let [x, y, z] = axes(bbox).map(|a| scale(a, self.scale));
+        let (sx, cx) = (sin(x), cos(x));
+        let (sy, cy) = (sin(y), cos(y));
+        let (sz, cz) = (sin(z), cos(z));
+        let v = add(add(mul(sx, cy), mul(sy, cz)), mul(sz, cx));
+        shift(v, -self.threshold)
+    }
+    fn lipschitz(&self) -> S {
+        // Each component of the gradient is at most sqrt(2) * scale.
+        self.scale.abs() * From::from(6f32.sqrt())
     }
 }
 
@@ -274,14 +306,22 @@ impl<S: na::RealField + Copy + Debug + From<f32>> ImplicitFunction<S> for Schwar
         (p.x * self.scale).cos() + (p.y * self.scale).cos() + (p.z * self.scale).cos()
             - self.threshold
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+    fn value_and_gradient(&self, p: &na::Point3<S>, _epsilon: S) -> (S, na::Vector3<S>) {
         let s = self.scale;
-        na::Vector3::new(
-            -s * (p.x * s).sin(),
-            -s * (p.y * s).sin(),
-            -s * (p.z * s).sin(),
+        let (sx, cx) = (p.x * s).sin_cos();
+        let (sy, cy) = (p.y * s).sin_cos();
+        let (sz, cz) = (p.z * s).sin_cos();
+        (
+            cx + cy + cz - self.threshold,
+            na::Vector3::new(-s * sx, -s * sy, -s * sz),
         )
-        .normalize()
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let [x, y, z] = axes(bbox).map(|a| cos(scale(a, self.scale)));
+        shift(add(add(x, y), z), -self.threshold)
+    }
+    fn lipschitz(&self) -> S {
+        self.scale.abs() * From::from(3f32.sqrt())
     }
 }
 
@@ -329,13 +369,48 @@ where
             vb
         }
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+    fn normal(&self, p: &na::Point3<S>, epsilon: S) -> na::Vector3<S> {
         if self.a.value(p) <= self.b.value(p) {
-            self.a.normal(p)
+            self.a.normal(p, epsilon)
+        } else {
+            self.b.normal(p, epsilon)
+        }
+    }
+    fn value_and_gradient(&self, p: &na::Point3<S>, epsilon: S) -> (S, na::Vector3<S>) {
+        let a = self.a.value_and_gradient(p, epsilon);
+        let b = self.b.value_and_gradient(p, epsilon);
+        if a.0 <= b.0 {
+            a
         } else {
-            self.b.normal(p)
+            b
         }
     }
+    fn value_batch(&self, points: &[na::Point3<S>], values: &mut [S]) {
+        csg_value_batch(&self.a, &self.b, points, values, |va, vb| {
+            if va <= vb {
+                va
+            } else {
+                vb
+            }
+        });
+    }
+    fn normal_batch(&self, points: &[na::Point3<S>], normals: &mut [na::Vector3<S>], epsilon: S) {
+        csg_normal_batch(
+            &self.a,
+            &self.b,
+            points,
+            normals,
+            |va, vb| va <= vb,
+            S::one(),
+            epsilon,
+        );
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        min(self.a.value_interval(bbox), self.b.value_interval(bbox))
+    }
+    fn lipschitz(&self) -> S {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
 }
 
 /// CSG intersection of two implicit functions (boolean AND).
@@ -378,13 +453,48 @@ where
             vb
         }
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+    fn normal(&self, p: &na::Point3<S>, epsilon: S) -> na::Vector3<S> {
         if self.a.value(p) >= self.b.value(p) {
-            self.a.normal(p)
+            self.a.normal(p, epsilon)
+        } else {
+            self.b.normal(p, epsilon)
+        }
+    }
+    fn value_and_gradient(&self, p: &na::Point3<S>, epsilon: S) -> (S, na::Vector3<S>) {
+        let a = self.a.value_and_gradient(p, epsilon);
+        let b = self.b.value_and_gradient(p, epsilon);
+        if a.0 >= b.0 {
+            a
         } else {
-            self.b.normal(p)
+            b
         }
     }
+    fn value_batch(&self, points: &[na::Point3<S>], values: &mut [S]) {
+        csg_value_batch(&self.a, &self.b, points, values, |va, vb| {
+            if va >= vb {
+                va
+            } else {
+                vb
+            }
+        });
+    }
+    fn normal_batch(&self, points: &[na::Point3<S>], normals: &mut [na::Vector3<S>], epsilon: S) {
+        csg_normal_batch(
+            &self.a,
+            &self.b,
+            points,
+            normals,
+            |va, vb| va >= vb,
+            S::one(),
+            epsilon,
+        );
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        max(self.a.value_interval(bbox), self.b.value_interval(bbox))
+    }
+    fn lipschitz(&self) -> S {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
 }
 
 /// CSG subtraction: `a` minus `b` (boolean A AND NOT B).
@@ -427,15 +537,51 @@ where
             neg_vb
         }
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+    fn normal(&self, p: &na::Point3<S>, epsilon: S) -> na::Vector3<S> {
         let va = self.a.value(p);
         let neg_vb = -self.b.value(p);
         if va >= neg_vb {
-            self.a.normal(p)
+            self.a.normal(p, epsilon)
+        } else {
+            -self.b.normal(p, epsilon)
+        }
+    }
+    fn value_and_gradient(&self, p: &na::Point3<S>, epsilon: S) -> (S, na::Vector3<S>) {
+        let a = self.a.value_and_gradient(p, epsilon);
+        let (vb, gb) = self.b.value_and_gradient(p, epsilon);
+        if a.0 >= -vb {
+            a
         } else {
-            -self.b.normal(p)
+            (-vb, -gb)
         }
     }
+    fn value_batch(&self, points: &[na::Point3<S>], values: &mut [S]) {
+        csg_value_batch(&self.a, &self.b, points, values, |va, vb| {
+            if va >= -vb {
+                va
+            } else {
+                -vb
+            }
+        });
+    }
+    fn normal_batch(&self, points: &[na::Point3<S>], normals: &mut [na::Vector3<S>], epsilon: S) {
+        csg_normal_batch(
+            &self.a,
+            &self.b,
+            points,
+            normals,
+            |va, vb| va >= -vb,
+            -S::one(),
+            epsilon,
+        );
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let (lb, ub) = self.b.value_interval(bbox);
+        max(self.a.value_interval(bbox), (-ub, -lb))
+    }
+    fn lipschitz(&self) -> S {
+        self.a.lipschitz().max(self.b.lipschitz())
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -493,13 +639,195 @@ where
         );
         self.inner.value(&q)
     }
-    fn normal(&self, p: &na::Point3<S>) -> na::Vector3<S> {
+    fn normal(&self, p: &na::Point3<S>, epsilon: S) -> na::Vector3<S> {
         let q = na::Point3::new(
             p.x - self.offset.x,
             p.y - self.offset.y,
             p.z - self.offset.z,
         );
-        self.inner.normal(&q)
+        self.inner.normal(&q, epsilon)
+    }
+    fn value_and_gradient(&self, p: &na::Point3<S>, epsilon: S) -> (S, na::Vector3<S>) {
+        self.inner.value_and_gradient(&(p - self.offset), epsilon)
+    }
+    fn value_batch(&self, points: &[na::Point3<S>], values: &mut [S]) {
+        let moved: Vec<_> = points.iter().map(|p| p - self.offset).collect();
+        self.inner.value_batch(&moved, values)
+    }
+    fn normal_batch(&self, points: &[na::Point3<S>], normals: &mut [na::Vector3<S>], epsilon: S) {
+        let moved: Vec<_> = points.iter().map(|p| p - self.offset).collect();
+        self.inner.normal_batch(&moved, normals, epsilon)
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        self.inner.value_interval(&BoundingBox::new(
+            &(bbox.min - self.offset),
+            &(bbox.max - self.offset),
+        ))
+    }
+    fn lipschitz(&self) -> S {
+        self.inner.lipschitz()
+    }
+}
+
+/// Divides a function by an estimate of its Lipschitz constant, so that it can be sampled like a
+/// distance field.
+///
+/// The constant is estimated from finite difference gradients on a regular grid of points over
+/// the bounding box, with a safety margin of 25% for steeper spots between the samples.
+pub struct Normalize<S: na::Scalar, T> {
+    inner: T,
+    gradient_bound: S,
+}
+
+impl<S, T> Normalize<S, T>
+where
+    S: na::RealField + Copy + Debug + From<f32>,
+    T: ImplicitFunction<S>,
+{
+    /// Normalize `inner`, sampling its gradient at 16 points along each axis.
+    pub fn new(inner: T) -> Self {
+        Normalize::with_samples(inner, 16)
+    }
+    /// Normalize `inner`, sampling its gradient at `samples` points along each axis.
+    pub fn with_samples(inner: T, samples: usize) -> Self {
+        let samples = samples.max(2);
+        let bbox = inner.bbox().clone();
+        let intervals: S = From::from((samples - 1) as f32);
+        let step = bbox.dim() / intervals;
+        let eps = step.min() * From::from(0.01f32);
+        let points = grid_points(bbox.min, step, samples);
+        // Central differences along each axis, evaluated in two batches per axis.
+        let mut squared = vec![S::zero(); points.len()];
+        let mut forward = vec![S::zero(); points.len()];
+        let mut backward = vec![S::zero(); points.len()];
+        for axis in 0..3 {
+            let mut delta = na::Vector3::zeros();
+            delta[axis] = eps;
+            let moved: Vec<_> = points.iter().map(|p| p + delta).collect();
+            inner.value_batch(&moved, &mut forward);
+            let moved: Vec<_> = points.iter().map(|p| p - delta).collect();
+            inner.value_batch(&moved, &mut backward);
+            for ((s, f), b) in squared.iter_mut().zip(&forward).zip(&backward) {
+                let d = (*f - *b) / (eps + eps);
+                *s += d * d;
+            }
+        }
+        let max_gradient = squared
+            .into_iter()
+            .fold(S::zero(), |m, s| if s > m { s } else { m })
+            .sqrt();
+        let gradient_bound = if max_gradient > S::zero() {
+            max_gradient * From::from(1.25f32)
+        } else {
+            S::one()
+        };
+        Normalize {
+            inner,
+            gradient_bound,
+        }
+    }
+    /// The estimated Lipschitz constant of the inner function, by which its values are divided.
+    pub fn gradient_bound(&self) -> S {
+        self.gradient_bound
+    }
+}
+
+impl<S, T> ImplicitFunction<S> for Normalize<S, T>
+where
+    S: na::RealField + Copy + Debug + From<f32>,
+    T: ImplicitFunction<S>,
+{
+    fn bbox(&self) -> &BoundingBox<S> {
+        self.inner.bbox()
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        lipschitz_normalize(self.inner.value(p), self.gradient_bound)
+    }
+    fn normal(&self, p: &na::Point3<S>, epsilon: S) -> na::Vector3<S> {
+        self.inner.normal(p, epsilon)
+    }
+    fn value_and_gradient(&self, p: &na::Point3<S>, epsilon: S) -> (S, na::Vector3<S>) {
+        let (value, gradient) = self.inner.value_and_gradient(p, epsilon);
+        (
+            lipschitz_normalize(value, self.gradient_bound),
+            gradient.map(|g| lipschitz_normalize(g, self.gradient_bound)),
+        )
+    }
+    fn value_batch(&self, points: &[na::Point3<S>], values: &mut [S]) {
+        self.inner.value_batch(points, values);
+        for v in values.iter_mut() {
+            *v = lipschitz_normalize(*v, self.gradient_bound);
+        }
+    }
+    fn normal_batch(&self, points: &[na::Point3<S>], normals: &mut [na::Vector3<S>], epsilon: S) {
+        self.inner.normal_batch(points, normals, epsilon)
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let (lower, upper) = self.inner.value_interval(bbox);
+        // Without a bound, there is nothing to normalize.
+        if is_unbounded(&lower, &upper) {
+            return (lower, upper);
+        }
+        (
+            lipschitz_normalize(lower, self.gradient_bound),
+            lipschitz_normalize(upper, self.gradient_bound),
+        )
+    }
+}
+
+/// Evaluates a function that is generic over its scalar with [`Dual`] numbers, which yields the
+/// exact gradient along with the value in a single pass.
+///
+/// ```rust
+/// use tessellation::{sdf, Dual, ImplicitFunction};
+/// use nalgebra as na;
+///
+/// let torus = sdf::AutoDiff::new(sdf::Torus::<Dual<f64>>::new(1.0.into(), 0.25.into()));
+/// let (value, gradient) = torus.value_and_gradient(&na::Point3::new(1.25, 0.0, 0.0), 1e-4);
+/// assert_eq!((value, gradient), (0.0, na::Vector3::x()));
+/// ```
+pub struct AutoDiff<S: na::Scalar, F> {
+    inner: F,
+    bbox: BoundingBox<S>,
+}
+
+impl<S, F> AutoDiff<S, F>
+where
+    S: na::RealField + Copy + Debug + From<f32>,
+    F: ImplicitFunction<Dual<S>>,
+{
+    /// Differentiate `inner` automatically.
+    pub fn new(inner: F) -> Self {
+        let bbox = inner.bbox();
+        let bbox = BoundingBox::new(&bbox.min.map(|c| c.value), &bbox.max.map(|c| c.value));
+        AutoDiff { inner, bbox }
+    }
+}
+
+impl<S, F> ImplicitFunction<S> for AutoDiff<S, F>
+where
+    S: na::RealField + Copy + Debug + From<f32>,
+    F: ImplicitFunction<Dual<S>>,
+{
+    fn bbox(&self) -> &BoundingBox<S> {
+        &self.bbox
+    }
+    fn value(&self, p: &na::Point3<S>) -> S {
+        self.inner.value(&p.map(Dual::constant)).value
+    }
+    fn value_and_gradient(&self, p: &na::Point3<S>, _epsilon: S) -> (S, na::Vector3<S>) {
+        let value = self.inner.value(&Dual::point(p));
+        (value.value, value.gradient)
+    }
+    fn value_interval(&self, bbox: &BoundingBox<S>) -> (S, S) {
+        let (lower, upper) = self.inner.value_interval(&BoundingBox::new(
+            &bbox.min.map(Dual::constant),
+            &bbox.max.map(Dual::constant),
+        ));
+        (lower.value, upper.value)
+    }
+    fn lipschitz(&self) -> S {
+        self.inner.lipschitz().value
     }
 }
 
@@ -507,17 +835,458 @@ where
 // Utilities
 // ---------------------------------------------------------------------------
 
-/// Compute the normal of an implicit function at a point using central finite differences.
+// Divide value by the bound of the gradient magnitude, so it becomes a lower bound of the
+// distance to the surface.
+fn lipschitz_normalize<S: na::RealField + Copy>(value: S, gradient_bound: S) -> S {
+    let gradient_bound = gradient_bound.abs();
+    if gradient_bound > S::zero() {
+        value / gradient_bound
+    } else {
+        value
+    }
+}
+
+// Interval arithmetic on (lower, upper) bounds, used by the value_interval implementations.
+
+fn axes<S: na::RealField + Copy>(bbox: &BoundingBox<S>) -> [(S, S); 3] {
+    std::array::from_fn(|i| (bbox.min[i], bbox.max[i]))
+}
+
+fn add<S: na::RealField + Copy>(a: (S, S), b: (S, S)) -> (S, S) {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn shift<S: na::RealField + Copy>(a: (S, S), offset: S) -> (S, S) {
+    (a.0 + offset, a.1 + offset)
+}
+
+fn scale<S: na::RealField + Copy>(a: (S, S), factor: S) -> (S, S) {
+    if factor >= S::zero() {
+        (a.0 * factor, a.1 * factor)
+    } else {
+        (a.1 * factor, a.0 * factor)
+    }
+}
+
+fn mul<S: na::RealField + Copy>(a: (S, S), b: (S, S)) -> (S, S) {
+    let products = [a.0 * b.0, a.0 * b.1, a.1 * b.0, a.1 * b.1];
+    let lower = products.iter().fold(products[0], |m, &p| m.min(p));
+    let upper = products.iter().fold(products[0], |m, &p| m.max(p));
+    (lower, upper)
+}
+
+fn min<S: na::RealField + Copy>(a: (S, S), b: (S, S)) -> (S, S) {
+    (a.0.min(b.0), a.1.min(b.1))
+}
+
+fn max<S: na::RealField + Copy>(a: (S, S), b: (S, S)) -> (S, S) {
+    (a.0.max(b.0), a.1.max(b.1))
+}
+
+fn abs<S: na::RealField + Copy>(a: (S, S)) -> (S, S) {
+    if a.0 >= S::zero() {
+        a
+    } else if a.1 <= S::zero() {
+        (-a.1, -a.0)
+    } else {
+        (S::zero(), a.1.max(-a.0))
+    }
+}
+
+// Bounds the euclidean norm of a vector whose components lie in the given intervals.
+fn norm<S: na::RealField + Copy>(components: &[(S, S)]) -> (S, S) {
+    let (lower, upper) = components
+        .iter()
+        .map(|&c| abs(c))
+        .fold((S::zero(), S::zero()), |(l, u), (cl, cu)| {
+            (l + cl * cl, u + cu * cu)
+        });
+    (lower.sqrt(), upper.sqrt())
+}
+
+fn sin<S: na::RealField + Copy>(a: (S, S)) -> (S, S) {
+    let one = S::one();
+    if a.1 - a.0 >= S::two_pi() {
+        return (-one, one);
+    }
+    let mut lower = a.0.sin().min(a.1.sin());
+    let mut upper = a.0.sin().max(a.1.sin());
+    // The first maximum at pi/2 + 2k pi and the first minimum at -pi/2 + 2k pi above a.0.
+    let period = S::two_pi();
+    let first_max = ((a.0 - S::frac_pi_2()) / period).ceil() * period + S::frac_pi_2();
+    let first_min = ((a.0 + S::frac_pi_2()) / period).ceil() * period - S::frac_pi_2();
+    if first_max <= a.1 {
+        upper = one;
+    }
+    if first_min <= a.1 {
+        lower = -one;
+    }
+    (lower, upper)
+}
+
+fn cos<S: na::RealField + Copy>(a: (S, S)) -> (S, S) {
+    sin(shift(a, S::frac_pi_2()))
+}
+
+// Evaluates both operands of a CSG operation on a batch of points and combines the values.
+fn csg_value_batch<S, A, B>(
+    a: &A,
+    b: &B,
+    points: &[na::Point3<S>],
+    values: &mut [S],
+    combine: impl Fn(S, S) -> S,
+) where
+    S: na::RealField + Copy + Debug,
+    A: ImplicitFunction<S>,
+    B: ImplicitFunction<S>,
+{
+    let mut vb = vec![S::zero(); points.len()];
+    a.value_batch(points, values);
+    b.value_batch(points, &mut vb);
+    for (v, vb) in values.iter_mut().zip(vb) {
+        *v = combine(*v, vb);
+    }
+}
+
+// Computes the normals of a CSG operation on a batch of points. Each point takes the normal of
+// the operand that determines its value, which is `a` where `use_a(value_a, value_b)` holds.
+// The normals of `b` are scaled by `sign_b`.
+fn csg_normal_batch<S, A, B>(
+    a: &A,
+    b: &B,
+    points: &[na::Point3<S>],
+    normals: &mut [na::Vector3<S>],
+    use_a: impl Fn(S, S) -> bool,
+    sign_b: S,
+    epsilon: S,
+) where
+    S: na::RealField + Copy + Debug,
+    A: ImplicitFunction<S>,
+    B: ImplicitFunction<S>,
+{
+    let mut va = vec![S::zero(); points.len()];
+    let mut vb = vec![S::zero(); points.len()];
+    a.value_batch(points, &mut va);
+    b.value_batch(points, &mut vb);
+    let (from_a, from_b): (Vec<usize>, Vec<usize>) =
+        (0..points.len()).partition(|&i| use_a(va[i], vb[i]));
+    gather_normals(a, points, &from_a, S::one(), normals, epsilon);
+    gather_normals(b, points, &from_b, sign_b, normals, epsilon);
+}
+
+// Computes the normals of f at the selected points in one batch.
+fn gather_normals<S, F>(
+    f: &F,
+    points: &[na::Point3<S>],
+    selected: &[usize],
+    sign: S,
+    normals: &mut [na::Vector3<S>],
+    epsilon: S,
+) where
+    S: na::RealField + Copy + Debug,
+    F: ImplicitFunction<S>,
+{
+    if selected.is_empty() {
+        return;
+    }
+    let selected_points: Vec<_> = selected.iter().map(|&i| points[i]).collect();
+    let mut selected_normals = vec![na::Vector3::zeros(); selected.len()];
+    f.normal_batch(&selected_points, &mut selected_normals, epsilon);
+    for (&i, n) in selected.iter().zip(selected_normals) {
+        normals[i] = n * sign;
+    }
+}
+
+// Returns samples^3 points on a regular grid starting at min.
+fn grid_points<S: na::RealField + Copy + From<f32>>(
+    min: na::Point3<S>,
+    step: na::Vector3<S>,
+    samples: usize,
+) -> Vec<na::Point3<S>> {
+    let mut points = Vec::with_capacity(samples.pow(3));
+    for z in 0..samples {
+        for y in 0..samples {
+            for x in 0..samples {
+                let offset = na::Vector3::new(x, y, z).map(|c| S::from(c as f32));
+                points.push(min + step.component_mul(&offset));
+            }
+        }
+    }
+    points
+}
+
+/// Compute the normal of an implicit function at a point using central finite differences
+/// with an offset of 1e-4. The default [`ImplicitFunction::normal`] does the same with the offset
+/// it is given.
 pub fn finite_difference_normal<S: na::RealField + Copy + Debug + From<f32>>(
     f: &dyn ImplicitFunction<S>,
     p: &na::Point3<S>,
 ) -> na::Vector3<S> {
-    let eps: S = From::from(0.0001f32);
-    let dx = f.value(&na::Point3::new(p.x + eps, p.y, p.z))
-        - f.value(&na::Point3::new(p.x - eps, p.y, p.z));
-    let dy = f.value(&na::Point3::new(p.x, p.y + eps, p.z))
-        - f.value(&na::Point3::new(p.x, p.y - eps, p.z));
-    let dz = f.value(&na::Point3::new(p.x, p.y, p.z + eps))
-        - f.value(&na::Point3::new(p.x, p.y, p.z - eps));
-    na::Vector3::new(dx, dy, dz).normalize()
+    crate::central_differences(f, p, From::from(0.0001f32)).normalize()
+}
+
+/// A point where [`check_normals`] found the normal of a function to disagree with its gradient.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NormalMismatch<S: na::Scalar> {
+    /// The sampled point.
+    pub point: na::Point3<S>,
+    /// The normal returned by the function.
+    pub normal: na::Vector3<S>,
+    /// The direction of the gradient, approximated with central differences.
+    pub numerical: na::Vector3<S>,
+    /// The angle between both directions in radians.
+    pub angle: S,
+}
+
+/// Compare the normals of `f` with the direction of its numerical gradient.
+///
+/// The function is sampled at the centers of a grid of `samples` cells along each axis of its
+/// bounding box. Returns the points where the angle between both exceeds `max_angle` radians,
+/// ordered from worst to best. Points where the function is not smooth, such as the creases of
+/// CSG operations, are skipped, since the numerical gradient is not meaningful there.
+pub fn check_normals<S: na::RealField + Copy + Debug + From<f32>>(
+    f: &dyn ImplicitFunction<S>,
+    samples: usize,
+    max_angle: S,
+) -> Vec<NormalMismatch<S>> {
+    let samples = samples.max(1);
+    let bbox = f.bbox();
+    let cells: S = From::from(samples as f32);
+    let step = bbox.dim() / cells;
+    let half: S = From::from(0.5f32);
+    let min = bbox.min + step * half;
+    let eps = step.min() * From::from(0.001f32);
+    let mut mismatches = Vec::new();
+    for point in grid_points(min, step, samples) {
+        let gradient = crate::central_differences(f, &point, eps);
+        let coarse = crate::central_differences(f, &point, eps * From::from(10f32));
+        // Where the gradient vanishes or depends on the offset, the function is not smooth.
+        let smooth = gradient.angle(&coarse) <= max_angle * half;
+        if gradient.norm() < From::from(1e-6f32) || !smooth {
+            continue;
+        }
+        let normal = f.normal(&point, eps);
+        let numerical = gradient.normalize();
+        let angle = normal.angle(&numerical);
+        // NaN normals do not agree either.
+        let agrees = angle <= max_angle;
+        if !agrees {
+            mismatches.push(NormalMismatch {
+                point,
+                normal,
+                numerical,
+                angle,
+            });
+        }
+    }
+    mismatches.sort_by(|a, b| b.angle.partial_cmp(&a.angle).unwrap_or(Ordering::Equal));
+    mismatches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn batches_match_single_evaluation() {
+        let shape = Subtraction::new(
+            Union::new(
+                Sphere::new(1.0),
+                Translate::new(Torus::new(1.0, 0.3), na::Vector3::new(0.5, 0., 0.)),
+            ),
+            Intersection::new(Cylinder::new(0.4, 2.0), Sphere::new(1.2)),
+        );
+        let points: Vec<_> = (0..100)
+            .map(|i| {
+                let t = i as f64 * 0.37;
+                na::Point3::new(t.sin() * 1.3, (t * 1.7).cos(), (t * 0.3).sin() * 0.9)
+            })
+            .collect();
+        let mut values = vec![0.; points.len()];
+        let mut normals = vec![na::Vector3::zeros(); points.len()];
+        shape.value_batch(&points, &mut values);
+        shape.normal_batch(&points, &mut normals, 1e-4);
+        for ((p, v), n) in points.iter().zip(values).zip(normals) {
+            assert_eq!(v, shape.value(p));
+            assert_eq!(n, shape.normal(p, 1e-4));
+        }
+    }
+
+    fn assert_interval_contains_values(f: &dyn ImplicitFunction<f64>) {
+        for i in 0..50 {
+            let t = i as f64 * 0.61;
+            let min = na::Point3::new(t.sin() * 1.5, (t * 1.3).cos() * 1.5, (t * 0.7).sin());
+            let size = 0.05 + (t * 2.1).sin().abs() * 0.8;
+            let bbox = BoundingBox::new(&min, &(min + na::Vector3::new(size, size, size)));
+            let (lower, upper) = f.value_interval(&bbox);
+            for j in 0..27 {
+                let fraction = na::Vector3::new(j % 3, j / 3 % 3, j / 9).map(|c| c as f64 / 2.);
+                let v = f.value(&(min + fraction * size));
+                // Up to rounding, since the interval is computed along a different path.
+                assert!(
+                    lower - 1e-12 <= v && v <= upper + 1e-12,
+                    "{} not in {:?}",
+                    v,
+                    (lower, upper)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn intervals_contain_values() {
+        assert_interval_contains_values(&Sphere::new(1.0));
+        assert_interval_contains_values(&RoundedBox::new(na::Vector3::new(0.5, 0.3, 0.8), 0.1));
+        assert_interval_contains_values(&Torus::new(1.0, 0.3));
+        assert_interval_contains_values(&Cylinder::new(0.4, 0.7));
+        assert_interval_contains_values(&Gyroid::new(3.0, 0.2, 2.0));
+        assert_interval_contains_values(&SchwartzP::new(3.0, 0.1, 2.0));
+        assert_interval_contains_values(&Subtraction::new(
+            Union::new(
+                Sphere::new(1.0),
+                Translate::new(Torus::new(1.0, 0.3), na::Vector3::new(0.5, 0., 0.)),
+            ),
+            Intersection::new(Cylinder::new(0.4, 2.0), Sphere::new(1.2)),
+        ));
+    }
+
+    #[test]
+    fn built_in_normals_match_gradients() {
+        let shapes: [&dyn ImplicitFunction<f64>; 5] = [
+            &Sphere::new(1.0),
+            &Torus::new(1.0, 0.3),
+            &Gyroid::new(3.0, 0.2, 2.0),
+            &SchwartzP::new(3.0, 0.1, 2.0),
+            &Subtraction::new(
+                Union::new(
+                    Sphere::new(1.0),
+                    Translate::new(Torus::new(1.0, 0.3), na::Vector3::new(0.5, 0., 0.)),
+                ),
+                Intersection::new(Cylinder::new(0.4, 2.0), Sphere::new(1.2)),
+            ),
+        ];
+        for shape in shapes {
+            assert_eq!(check_normals(shape, 12, 0.01), []);
+        }
+    }
+
+    fn assert_gradients_match<F, G>(f: F, g: G)
+    where
+        F: ImplicitFunction<f64>,
+        G: ImplicitFunction<Dual<f64>>,
+    {
+        let g = AutoDiff::new(g);
+        assert_eq!(f.bbox(), g.bbox());
+        for i in 0..50 {
+            let t = i as f64 * 0.43;
+            let p = na::Point3::new(t.sin() * 1.3, (t * 1.9).cos(), (t * 0.6).sin() * 0.9);
+            let (value, gradient) = f.value_and_gradient(&p, 1e-4);
+            assert_relative_eq!(value, f.value(&p), epsilon = 1e-12);
+            assert_relative_eq!(value, g.value(&p), epsilon = 1e-12);
+            assert_relative_eq!(gradient, g.value_and_gradient(&p, 1e-4).1, epsilon = 1e-12);
+        }
+    }
+
+    #[test]
+    fn analytic_gradients_match_automatic_differentiation() {
+        let dual = |v: f64| Dual::from(v as f32);
+        assert_gradients_match(Sphere::new(1.0), Sphere::new(dual(1.0)));
+        assert_gradients_match(Torus::new(1.0, 0.25), Torus::new(dual(1.0), dual(0.25)));
+        assert_gradients_match(
+            Gyroid::new(3.0, 0.5, 2.0),
+            Gyroid::new(dual(3.0), dual(0.5), dual(2.0)),
+        );
+        assert_gradients_match(
+            SchwartzP::new(3.0, 0.5, 2.0),
+            SchwartzP::new(dual(3.0), dual(0.5), dual(2.0)),
+        );
+        assert_gradients_match(
+            Subtraction::new(
+                Translate::new(Sphere::new(1.0), na::Vector3::new(0.5, 0., 0.)),
+                Union::new(Torus::new(1.0, 0.25), Sphere::new(0.5)),
+            ),
+            Subtraction::new(
+                Translate::new(
+                    Sphere::new(dual(1.0)),
+                    na::Vector3::new(0.5, 0., 0.).map(dual),
+                ),
+                Union::new(Torus::new(dual(1.0), dual(0.25)), Sphere::new(dual(0.5))),
+            ),
+        );
+    }
+
+    #[test]
+    fn automatic_differentiation() {
+        // Neither the rounded box nor the cylinder compute their gradient on their own.
+        let shape = AutoDiff::new(Intersection::new(
+            RoundedBox::new(na::Vector3::new(0.5, 0.3, 0.8).map(Dual::from), 0.1.into()),
+            Cylinder::new(0.6.into(), 1.0.into()),
+        ));
+        assert_eq!(check_normals(&shape, 12, 0.01), []);
+        let mesh = crate::ManifoldDualContouring::new(&shape, 0.1, 0.1)
+            .tessellate()
+            .unwrap();
+        mesh.is_closed().unwrap();
+    }
+
+    // A sphere whose normal mixes up the x and y axes.
+    struct SwappedNormal(Sphere<f64>);
+    impl ImplicitFunction<f64> for SwappedNormal {
+        fn bbox(&self) -> &BoundingBox<f64> {
+            self.0.bbox()
+        }
+        fn value(&self, p: &na::Point3<f64>) -> f64 {
+            self.0.value(p)
+        }
+        fn normal(&self, p: &na::Point3<f64>, epsilon: f64) -> na::Vector3<f64> {
+            let n = self.0.normal(p, epsilon);
+            na::Vector3::new(n.y, n.x, n.z)
+        }
+    }
+
+    #[test]
+    fn unbounded_intervals() {
+        let max = f64::MAX;
+        assert!(crate::is_unbounded(&-max, &max));
+        assert!(crate::is_unbounded(&(-0.9 * max), &(0.9 * max)));
+        assert!(crate::is_unbounded(&f64::NEG_INFINITY, &1.));
+        assert!(crate::is_unbounded(&f64::NAN, &1.));
+        assert!(!crate::is_unbounded(&-1., &1.));
+        // Normalizing keeps a missing bound.
+        let normalized = Normalize::new(SwappedNormal(Sphere::new(1.0)));
+        let (lower, upper) = normalized.value_interval(Sphere::new(1.0).bbox());
+        assert!(crate::is_unbounded(&lower, &upper));
+    }
+
+    #[test]
+    fn reports_wrong_normals() {
+        let mismatches = check_normals(&SwappedNormal(Sphere::new(1.0)), 8, 0.01);
+        assert!(!mismatches.is_empty());
+        assert!(mismatches.windows(2).all(|w| w[0].angle >= w[1].angle));
+        let worst = &mismatches[0];
+        // Only points off the diagonal x == y are affected.
+        assert!((worst.point.x - worst.point.y).abs() > 0.1);
+        assert_relative_eq!(
+            worst.numerical,
+            Sphere::new(1.0).normal(&worst.point, 1e-4),
+            epsilon = 1e-6
+        );
+    }
+
+    #[test]
+    fn intervals_are_tight_for_distances() {
+        // The interval of a box far away from a sphere is the exact range of distances.
+        let sphere = Sphere::new(1.0);
+        let bbox = BoundingBox::new(&na::Point3::new(3., 0., 0.), &na::Point3::new(4., 0., 0.));
+        assert_eq!(sphere.value_interval(&bbox), (2., 3.));
+        // An interval that spans a period of the lattice reaches the extremes.
+        let p = SchwartzP::new(1.0, 0.0, 10.0);
+        let bbox = BoundingBox::new(
+            &na::Point3::new(-7., -7., -7.),
+            &na::Point3::new(0., 0., 0.),
+        );
+        let (lower, upper) = p.value_interval(&bbox);
+        assert_relative_eq!(lower, -3., epsilon = 1e-6);
+        assert_relative_eq!(upper, 3., epsilon = 1e-6);
+    }
 }
@@ -0,0 +1,221 @@
+use crate::mesh::Mesh;
+use std::io::{self, Seek, SeekFrom, Write};
+
+/// Receives the vertices and faces of a mesh while it is generated.
+///
+/// Vertices are numbered in the order in which they are added, starting at zero. A face only
+/// refers to vertices that were added before it.
+pub trait MeshSink<S> {
+    /// Add a vertex.
+    fn add_vertex(&mut self, vertex: [S; 3]) -> io::Result<()>;
+    /// Add a triangle as indexes into the vertices.
+    fn add_face(&mut self, face: [usize; 3]) -> io::Result<()>;
+    /// Called once after the last face.
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S> MeshSink<S> for Mesh<S> {
+    fn add_vertex(&mut self, vertex: [S; 3]) -> io::Result<()> {
+        self.vertices.push(vertex);
+        Ok(())
+    }
+    fn add_face(&mut self, face: [usize; 3]) -> io::Result<()> {
+        self.faces.push(face);
+        Ok(())
+    }
+}
+
+fn to_f32<S: Copy>(v: [S; 3]) -> [f32; 3]
+where
+    f64: From<S>,
+{
+    v.map(|c| f64::from(c) as f32)
+}
+
+fn to_u32(i: usize) -> io::Result<u32> {
+    u32::try_from(i).map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "mesh too large"))
+}
+
+fn write_f32s<W: Write>(writer: &mut W, values: &[f32]) -> io::Result<()> {
+    for v in values {
+        writer.write_all(&v.to_le_bytes())?;
+    }
+    Ok(())
+}
+
+/// Writes a binary STL file.
+///
+/// Faces are written as they arrive. Vertex positions are kept in memory, since STL stores them
+/// with every face. The face count in the header is filled in by [`finish`](MeshSink::finish).
+pub struct StlWriter<W: Write + Seek> {
+    writer: W,
+    vertices: Vec<[f32; 3]>,
+    faces: u32,
+}
+
+impl<W: Write + Seek> StlWriter<W> {
+    /// Write the header to `writer` and return the sink.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        let mut header = [0u8; 80];
+        let title = b"binary STL written by tessellation";
+        header[..title.len()].copy_from_slice(title);
+        writer.write_all(&header)?;
+        writer.write_all(&0u32.to_le_bytes())?;
+        Ok(StlWriter {
+            writer,
+            vertices: Vec::new(),
+            faces: 0,
+        })
+    }
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<S: Copy, W: Write + Seek> MeshSink<S> for StlWriter<W>
+where
+    f64: From<S>,
+{
+    fn add_vertex(&mut self, vertex: [S; 3]) -> io::Result<()> {
+        self.vertices.push(to_f32(vertex));
+        Ok(())
+    }
+    fn add_face(&mut self, face: [usize; 3]) -> io::Result<()> {
+        let [a, b, c] = face.map(|i| nalgebra::Vector3::from(self.vertices[i]));
+        let normal = (b - a).cross(&(c - a));
+        let normal = normal.try_normalize(0.).unwrap_or(normal);
+        write_f32s(&mut self.writer, normal.as_slice())?;
+        for v in [a, b, c] {
+            write_f32s(&mut self.writer, v.as_slice())?;
+        }
+        self.writer.write_all(&0u16.to_le_bytes())?;
+        self.faces = self
+            .faces
+            .checked_add(1)
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "mesh too large"))?;
+        Ok(())
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        self.writer.seek(SeekFrom::Start(80))?;
+        self.writer.write_all(&self.faces.to_le_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+/// Writes a binary little endian PLY file.
+///
+/// The header declares the faces before the vertices, so faces are written as they arrive. Like
+/// in [`StlWriter`], vertex positions are kept in memory until [`finish`](MeshSink::finish)
+/// writes them and fills in the element counts of the header.
+pub struct PlyWriter<W: Write + Seek> {
+    writer: W,
+    vertices: Vec<[f32; 3]>,
+    faces: u32,
+}
+
+fn ply_header(faces: u32, vertices: usize) -> String {
+    // The counts are zero padded, so that the final header has the same length.
+    format!(
+        "ply\nformat binary_little_endian 1.0\ncomment written by tessellation\n\
+         element face {:010}\nproperty list uchar uint vertex_indices\n\
+         element vertex {:010}\nproperty float x\nproperty float y\nproperty float z\n\
+         end_header\n",
+        faces, vertices
+    )
+}
+
+impl<W: Write + Seek> PlyWriter<W> {
+    /// Write a preliminary header to `writer` and return the sink.
+    pub fn new(mut writer: W) -> io::Result<Self> {
+        writer.write_all(ply_header(0, 0).as_bytes())?;
+        Ok(PlyWriter {
+            writer,
+            vertices: Vec::new(),
+            faces: 0,
+        })
+    }
+    /// Return the underlying writer.
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+impl<S: Copy, W: Write + Seek> MeshSink<S> for PlyWriter<W>
+where
+    f64: From<S>,
+{
+    fn add_vertex(&mut self, vertex: [S; 3]) -> io::Result<()> {
+        self.vertices.push(to_f32(vertex));
+        Ok(())
+    }
+    fn add_face(&mut self, face: [usize; 3]) -> io::Result<()> {
+        self.writer.write_all(&[3u8])?;
+        for i in face {
+            self.writer.write_all(&to_u32(i)?.to_le_bytes())?;
+        }
+        self.faces = to_u32(self.faces as usize + 1)?;
+        Ok(())
+    }
+    fn finish(&mut self) -> io::Result<()> {
+        for vertex in &self.vertices {
+            write_f32s(&mut self.writer, vertex)?;
+        }
+        self.writer.seek(SeekFrom::Start(0))?;
+        self.writer
+            .write_all(ply_header(self.faces, self.vertices.len()).as_bytes())?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MeshSink, PlyWriter, StlWriter};
+    use std::io::Cursor;
+
+    fn tetrahedron<S: MeshSink<f64>>(sink: &mut S) {
+        for v in [[0., 0., 0.], [1., 0., 0.], [0., 1., 0.], [0., 0., 1.]] {
+            sink.add_vertex(v).unwrap();
+        }
+        for f in [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]] {
+            sink.add_face(f).unwrap();
+        }
+        sink.finish().unwrap();
+    }
+
+    #[test]
+    fn stl() {
+        let mut stl = StlWriter::new(Cursor::new(Vec::new())).unwrap();
+        tetrahedron(&mut stl);
+        let bytes = stl.into_inner().into_inner();
+        assert_eq!(bytes.len(), 84 + 4 * 50);
+        assert_eq!(bytes[80..84], 4u32.to_le_bytes());
+        // The normal of the first face points down.
+        assert_eq!(bytes[92..96], (-1f32).to_le_bytes());
+    }
+
+    #[test]
+    fn ply() {
+        let mut ply = PlyWriter::new(Cursor::new(Vec::new())).unwrap();
+        tetrahedron(&mut ply);
+        let bytes = ply.into_inner().into_inner();
+        let header_end = b"end_header\n";
+        let header_len = bytes
+            .windows(header_end.len())
+            .position(|w| w == header_end)
+            .unwrap()
+            + header_end.len();
+        let header = std::str::from_utf8(&bytes[..header_len]).unwrap();
+        assert!(header.contains("element vertex 0000000004\n"));
+        assert!(header.contains("element face 0000000004\n"));
+        assert!(header.find("element face") < header.find("element vertex"));
+        assert_eq!(bytes.len(), header_len + 4 * 13 + 4 * 12);
+        // The faces come first, the last vertex ends the file.
+        assert_eq!(bytes[header_len], 3);
+        assert_eq!(bytes[bytes.len() - 4..], 1f32.to_le_bytes());
+    }
+}
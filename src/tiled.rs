@@ -0,0 +1,345 @@
+use crate::{
+    cancellation::CancellationToken, config::TessellationConfig, error_field::ErrorField,
+    mesh::Mesh, vertex_index::VertexIndex, AsUSize, DualContouringError, ImplicitFunction,
+    ManifoldDualContouring, RealField,
+};
+use num_traits::Float;
+use std::collections::HashMap;
+
+// A corner of a face generated for a tile.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum TileVertex {
+    // Index into the vertices of the tile, or into the stitched mesh once added to a stitcher.
+    Own(usize),
+    // A cell of a neighboring tile.
+    Neighbor(VertexIndex),
+}
+
+/// The faces generated for one tile by [`TiledManifoldDualContouring`].
+///
+/// Faces along the lower walls of the tile refer to vertices of the neighboring tiles, so a
+/// single tile is not a mesh on its own. Add all tiles to a [`TileStitcher`] to get the mesh.
+#[derive(Clone, Debug)]
+pub struct TileMesh<S> {
+    pub(crate) tile: [usize; 3],
+    pub(crate) vertices: Vec<[S; 3]>,
+    pub(crate) polygons: Vec<Vec<TileVertex>>,
+    // Vertices of the cells in the upper layers of the tile, which the upper neighbors refer to.
+    pub(crate) exports: HashMap<VertexIndex, usize>,
+}
+
+impl<S> TileMesh<S> {
+    /// Index of the tile, as returned by [`TiledManifoldDualContouring::tiles`].
+    pub fn tile(&self) -> [usize; 3] {
+        self.tile
+    }
+    /// Number of vertices owned by the tile.
+    pub fn num_vertices(&self) -> usize {
+        self.vertices.len()
+    }
+}
+
+/// Merges [`TileMesh`]es into a single mesh.
+///
+/// Tiles can be added in any order. Faces across tile walls are emitted as soon as all tiles
+/// they touch were added, so only the vertices along the walls of missing tiles are kept
+/// around.
+#[derive(Clone, Debug)]
+pub struct TileStitcher<S> {
+    mesh: Mesh<S>,
+    exports: HashMap<VertexIndex, usize>,
+    pending: Vec<Vec<TileVertex>>,
+}
+
+impl<S> Default for TileStitcher<S> {
+    fn default() -> Self {
+        TileStitcher {
+            mesh: Mesh::default(),
+            exports: HashMap::new(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl<S: Copy> TileStitcher<S> {
+    /// Create an empty stitcher.
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add the faces and vertices of a tile.
+    pub fn add(&mut self, tile: TileMesh<S>) {
+        let offset = self.mesh.vertices.len();
+        self.mesh.vertices.extend(tile.vertices);
+        self.exports
+            .extend(tile.exports.into_iter().map(|(k, i)| (k, i + offset)));
+        let polygons = tile.polygons.into_iter().map(|polygon| {
+            polygon
+                .into_iter()
+                .map(|v| match v {
+                    TileVertex::Own(i) => TileVertex::Own(i + offset),
+                    neighbor => neighbor,
+                })
+                .collect()
+        });
+        let pending: Vec<_> = self.pending.drain(..).chain(polygons).collect();
+        for polygon in pending {
+            self.add_polygon(polygon);
+        }
+    }
+    /// Return the stitched mesh. Faces that touch tiles which were never added are dropped.
+    pub fn finish(self) -> Mesh<S> {
+        // Drop vertices that were only exported for tiles that were never added.
+        let mut used = vec![None; self.mesh.vertices.len()];
+        let mut vertices = Vec::new();
+        let mut faces = self.mesh.faces;
+        for face in &mut faces {
+            for i in face.iter_mut() {
+                *i = *used[*i].get_or_insert_with(|| {
+                    vertices.push(self.mesh.vertices[*i]);
+                    vertices.len() - 1
+                });
+            }
+        }
+        Mesh { vertices, faces }
+    }
+    fn add_polygon(&mut self, polygon: Vec<TileVertex>) {
+        let mut p = Vec::with_capacity(4);
+        for v in &polygon {
+            let i = match *v {
+                TileVertex::Own(i) => i,
+                TileVertex::Neighbor(ref vertex_index) => match self.exports.get(vertex_index) {
+                    Some(&i) => i,
+                    None => {
+                        self.pending.push(polygon);
+                        return;
+                    }
+                },
+            };
+            // Several cells of a neighbor may share one simplified vertex.
+            if !p.contains(&i) {
+                p.push(i);
+            }
+        }
+        if p.len() < 3 {
+            return;
+        }
+        self.mesh.faces.push([p[0], p[1], p[2]]);
+        if p.len() == 4 {
+            self.mesh.faces.push([p[2], p[3], p[0]]);
+        }
+    }
+}
+
+/// Tessellates the grid of [`ManifoldDualContouring`] in independent blocks of cells.
+///
+/// Only the state of one tile is held in memory at a time, which allows much finer resolutions.
+/// Tiles are aligned to the octree, and vertices are only merged within a tile, so the tiles
+/// fit together without cracks:
+///
+/// ```rust
+/// use tessellation::{sdf, TiledManifoldDualContouring};
+///
+/// let sphere = sdf::Sphere::new(1.0);
+/// let tiled = TiledManifoldDualContouring::new(&sphere, 0.05, 0.1, 16);
+/// let mesh = tiled.tessellate().unwrap();
+/// ```
+///
+/// To process or store tiles separately, call [`tessellate_tile`](Self::tessellate_tile) for
+/// each of [`tiles`](Self::tiles) and merge the results with a [`TileStitcher`].
+#[derive(Clone)]
+pub struct TiledManifoldDualContouring<'a, S: RealField> {
+    function: &'a dyn ImplicitFunction<S>,
+    res: S,
+    relative_error: S,
+    config: TessellationConfig<S>,
+    tile_size: usize,
+    error_field: Option<&'a dyn ErrorField<S>>,
+    cancellation: Option<CancellationToken>,
+}
+
+impl<'a, S: From<f32> + RealField + Float + AsUSize> TiledManifoldDualContouring<'a, S> {
+    /// Constructor
+    /// f: function to tessellate
+    /// res: resolution
+    /// relative_error: acceptable error threshold when simplifying the mesh.
+    /// tile_size: edge length of the tiles in cells, rounded up to a power of two.
+    pub fn new(
+        f: &'a dyn ImplicitFunction<S>,
+        res: S,
+        relative_error: S,
+        tile_size: usize,
+    ) -> TiledManifoldDualContouring<'a, S> {
+        TiledManifoldDualContouring::with_config(
+            f,
+            res,
+            relative_error,
+            tile_size,
+            TessellationConfig::default(),
+        )
+    }
+    /// Constructor with explicit tuning parameters, see [`TessellationConfig`].
+    pub fn with_config(
+        f: &'a dyn ImplicitFunction<S>,
+        res: S,
+        relative_error: S,
+        tile_size: usize,
+        config: TessellationConfig<S>,
+    ) -> TiledManifoldDualContouring<'a, S> {
+        TiledManifoldDualContouring {
+            function: f,
+            res,
+            relative_error,
+            config,
+            tile_size: tile_size.next_power_of_two(),
+            error_field: None,
+            cancellation: None,
+        }
+    }
+    /// Set a field that replaces the constant `relative_error`, see
+    /// [`ManifoldDualContouring::set_error_field`].
+    pub fn set_error_field(&mut self, field: &'a dyn ErrorField<S>) {
+        self.error_field = Some(field);
+    }
+    /// Set a token through which tessellation can be aborted.
+    pub fn set_cancellation_token(&mut self, token: CancellationToken) {
+        self.cancellation = Some(token);
+    }
+    /// Return the indexes of all tiles, in the order in which tessellate processes them.
+    pub fn tiles(&self) -> Vec<[usize; 3]> {
+        let dim = self.mdc().dim();
+        let count = dim.map(|d| d.div_ceil(self.tile_size));
+        let mut tiles = Vec::new();
+        for z in 0..count[2] {
+            for y in 0..count[1] {
+                for x in 0..count[0] {
+                    tiles.push([x, y, z]);
+                }
+            }
+        }
+        tiles
+    }
+    /// Tessellate a single tile.
+    ///
+    /// All tiles of a mesh must use the same `attempt`. If any tile fails with
+    /// [`DualContouringError::HitZero`], which only happens with
+    /// [`ZeroHandling::Restart`](crate::ZeroHandling::Restart), all tiles have to be tessellated again with the next
+    /// attempt, which shifts the grid the same way [`ManifoldDualContouring`] does on a retry.
+    ///
+    /// Fails with [`DualContouringError::Unsupported`] if the config asks to
+    /// [`preserve_topology`](TessellationConfig::preserve_topology), which needs the whole mesh.
+    pub fn tessellate_tile(
+        &self,
+        tile: [usize; 3],
+        attempt: usize,
+    ) -> Result<TileMesh<S>, DualContouringError> {
+        if self.config.preserve_topology {
+            return Err(DualContouringError::Unsupported(
+                "preserve_topology with tiled tessellation".to_string(),
+            ));
+        }
+        let mut mdc = self.mdc();
+        mdc.set_attempt(attempt);
+        let lower = tile.map(|t| t * self.tile_size);
+        let upper = lower.map(|l| l + self.tile_size);
+        let levels = self.tile_size.trailing_zeros() as usize;
+        mdc.tessellate_tile(tile, [lower, upper], levels)
+    }
+    /// Tessellate all tiles one after the other and stitch them together.
+    pub fn tessellate(&self) -> Result<Mesh<S>, DualContouringError> {
+        for attempt in 0..=self.config.max_retries {
+            match self.try_tessellate(attempt) {
+                Err(DualContouringError::HitZero(_)) => {}
+                result => return result,
+            }
+        }
+        Err(DualContouringError::RetriesExhausted(
+            self.config.max_retries + 1,
+        ))
+    }
+    fn try_tessellate(&self, attempt: usize) -> Result<Mesh<S>, DualContouringError> {
+        let mut stitcher = TileStitcher::new();
+        for tile in self.tiles() {
+            stitcher.add(self.tessellate_tile(tile, attempt)?);
+        }
+        let mesh = stitcher.finish();
+        if mesh.faces.is_empty() {
+            return Err(DualContouringError::EmptySurface);
+        }
+        Ok(mesh)
+    }
+    fn mdc(&self) -> ManifoldDualContouring<'a, S> {
+        let mut mdc = ManifoldDualContouring::with_config(
+            self.function,
+            self.res,
+            self.relative_error,
+            self.config.clone(),
+        );
+        if let Some(field) = self.error_field {
+            mdc.set_error_field(field);
+        }
+        if let Some(ref token) = self.cancellation {
+            mdc.set_cancellation_token(token.clone());
+        }
+        mdc
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TileStitcher, TiledManifoldDualContouring};
+    use crate::{
+        mesh::MeshError, sdf, Boundary, DualContouringError, ManifoldDualContouring,
+        TessellationConfig,
+    };
+
+    #[test]
+    fn matches_untiled_without_simplification() -> Result<(), MeshError> {
+        let sphere = sdf::Sphere::new(1.0);
+        let tiled = TiledManifoldDualContouring::new(&sphere, 0.2, 0.0, 4);
+        assert!(tiled.tiles().len() > 8);
+        let mesh = tiled.tessellate().unwrap();
+        let untiled = ManifoldDualContouring::new(&sphere, 0.2, 0.0)
+            .tessellate()
+            .unwrap();
+        assert_eq!(mesh.vertices.len(), untiled.vertices.len());
+        assert_eq!(mesh.faces.len(), untiled.faces.len());
+        mesh.is_closed()
+    }
+
+    #[test]
+    fn stitches_simplified_tiles() -> Result<(), MeshError> {
+        let sphere = sdf::Sphere::new(1.0);
+        let tiled = TiledManifoldDualContouring::new(&sphere, 0.1, 0.1, 4);
+        let mesh = tiled.tessellate().unwrap();
+        mesh.is_closed()?;
+        assert!(mesh.boundary_loops().is_empty());
+
+        // The order in which tiles are added does not matter.
+        let mut stitcher = TileStitcher::new();
+        for tile in tiled.tiles().into_iter().rev() {
+            stitcher.add(tiled.tessellate_tile(tile, 0).unwrap());
+        }
+        let reversed = stitcher.finish();
+        assert_eq!(mesh.faces.len(), reversed.faces.len());
+        reversed.is_closed()
+    }
+
+    #[test]
+    fn capped_tiles() -> Result<(), MeshError> {
+        let gyroid = sdf::Gyroid::new(std::f64::consts::PI, 0.0, 1.3);
+        let config = TessellationConfig::default().boundary(Boundary::Capped);
+        let tiled = TiledManifoldDualContouring::with_config(&gyroid, 0.1, 0.1, 8, config);
+        tiled.tessellate().unwrap().is_closed()
+    }
+
+    #[test]
+    fn rejects_topology_preservation() {
+        let sphere = sdf::Sphere::new(1.0);
+        let config = TessellationConfig::default().preserve_topology(true);
+        let tiled = TiledManifoldDualContouring::with_config(&sphere, 0.2, 0.1, 4, config);
+        assert!(matches!(
+            tiled.tessellate(),
+            Err(DualContouringError::Unsupported(_))
+        ));
+    }
+}
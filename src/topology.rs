@@ -0,0 +1,247 @@
+use std::{
+    collections::{BTreeSet, HashMap},
+    hash::Hash,
+};
+
+// The topology of a triangle mesh whose vertices are identified by keys of type T.
+#[derive(Debug, PartialEq)]
+pub(crate) struct Topology<T> {
+    // Vertices around which the mesh is not a disk or a half disk: endpoints of edges that are
+    // shared by more than two faces or by two faces of opposite orientation, and vertices whose
+    // faces form more than one fan.
+    pub defects: BTreeSet<T>,
+    // Euler characteristic and number of boundary loops of each connected component, sorted.
+    // Together they determine the genus of every component, since χ = 2 - 2g - b.
+    pub components: Vec<(i64, usize)>,
+}
+
+impl<T: Copy + Eq + Hash + Ord> Topology<T> {
+    pub fn new(faces: &[[T; 3]]) -> Self {
+        let mut directed_edges = HashMap::new();
+        let mut edges = HashMap::new();
+        let mut vertices = UnionFind::default();
+        for face in faces {
+            for i in 0..3 {
+                let (a, b) = (face[i], face[(i + 1) % 3]);
+                *directed_edges.entry((a, b)).or_insert(0) += 1;
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+                vertices.union(a, b);
+            }
+        }
+        let mut defects = BTreeSet::new();
+        for (&(a, b), &count) in &directed_edges {
+            if count > 1 || edges[&(a.min(b), a.max(b))] > 2 {
+                defects.insert(a);
+                defects.insert(b);
+            }
+        }
+        // The opposite edges of the faces around a vertex form its link, which is a single path
+        // or cycle where the mesh is manifold.
+        let mut links: HashMap<T, UnionFind<T>> = HashMap::new();
+        for face in faces {
+            for i in 0..3 {
+                links
+                    .entry(face[i])
+                    .or_default()
+                    .union(face[(i + 1) % 3], face[(i + 2) % 3]);
+            }
+        }
+        for (vertex, mut link) in links {
+            if link.count_sets() > 1 {
+                defects.insert(vertex);
+            }
+        }
+
+        let mut components: HashMap<T, (i64, usize)> = HashMap::new();
+        let keys: Vec<T> = vertices.parents.keys().copied().collect();
+        for vertex in keys {
+            let root = vertices.find(vertex);
+            components.entry(root).or_insert((0, 0)).0 += 1;
+        }
+        // Edges of a single face lie on the boundary and chain into its loops.
+        let mut boundary = UnionFind::default();
+        for (&(a, b), &count) in &edges {
+            let root = vertices.find(a);
+            components.get_mut(&root).unwrap().0 -= 1;
+            if count == 1 {
+                boundary.union(a, b);
+            }
+        }
+        for face in faces {
+            let root = vertices.find(face[0]);
+            components.get_mut(&root).unwrap().0 += 1;
+        }
+        let keys: Vec<T> = boundary.parents.keys().copied().collect();
+        for vertex in keys {
+            if boundary.find(vertex) == vertex {
+                let root = vertices.find(vertex);
+                components.get_mut(&root).unwrap().1 += 1;
+            }
+        }
+        let mut components: Vec<(i64, usize)> = components.into_values().collect();
+        components.sort_unstable();
+        Topology {
+            defects,
+            components,
+        }
+    }
+}
+
+// Disjoint sets with union by size and path compression, so that find stays shallow even for
+// long strips of faces.
+struct UnionFind<T> {
+    parents: HashMap<T, T>,
+    sizes: HashMap<T, usize>,
+}
+
+impl<T> Default for UnionFind<T> {
+    fn default() -> Self {
+        UnionFind {
+            parents: HashMap::new(),
+            sizes: HashMap::new(),
+        }
+    }
+}
+
+impl<T: Copy + Eq + Hash> UnionFind<T> {
+    fn find(&mut self, x: T) -> T {
+        let mut root = *self.parents.entry(x).or_insert(x);
+        while self.parents[&root] != root {
+            root = self.parents[&root];
+        }
+        let mut node = x;
+        while node != root {
+            node = self.parents.insert(node, root).unwrap();
+        }
+        root
+    }
+    fn union(&mut self, a: T, b: T) {
+        let (a, b) = (self.find(a), self.find(b));
+        if a == b {
+            return;
+        }
+        let size = |x| *self.sizes.get(&x).unwrap_or(&1);
+        let (small, large) = if size(a) < size(b) { (a, b) } else { (b, a) };
+        let merged = size(small) + size(large);
+        self.parents.insert(small, large);
+        self.sizes.insert(large, merged);
+        self.sizes.remove(&small);
+    }
+    fn count_sets(&mut self) -> usize {
+        let keys: Vec<T> = self.parents.keys().copied().collect();
+        keys.into_iter().filter(|&x| self.find(x) == x).count()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Topology;
+
+    const TETRAHEDRON: [[usize; 3]; 4] = [[0, 2, 1], [0, 1, 3], [1, 2, 3], [2, 0, 3]];
+
+    fn offset(faces: &[[usize; 3]], offset: usize) -> Vec<[usize; 3]> {
+        faces.iter().map(|f| f.map(|v| v + offset)).collect()
+    }
+
+    #[test]
+    fn closed_surfaces() {
+        let tetrahedron = Topology::new(&TETRAHEDRON);
+        assert!(tetrahedron.defects.is_empty());
+        assert_eq!(tetrahedron.components, vec![(2, 0)]);
+
+        let mut two = TETRAHEDRON.to_vec();
+        two.extend(offset(&TETRAHEDRON, 4));
+        assert_eq!(Topology::new(&two).components, vec![(2, 0), (2, 0)]);
+
+        // A torus made of a 3x3 grid of quads with opposite sides glued together.
+        let mut torus = Vec::new();
+        let v = |i: usize, j: usize| (i % 3) * 3 + j % 3;
+        for i in 0..3 {
+            for j in 0..3 {
+                torus.push([v(i, j), v(i + 1, j), v(i + 1, j + 1)]);
+                torus.push([v(i + 1, j + 1), v(i, j + 1), v(i, j)]);
+            }
+        }
+        let torus = Topology::new(&torus);
+        assert!(torus.defects.is_empty());
+        assert_eq!(torus.components, vec![(0, 0)]);
+    }
+
+    #[test]
+    fn open_surfaces() {
+        // A 3x3 grid of quads, and the same grid without its middle quad.
+        let v = |i: usize, j: usize| i * 4 + j;
+        let grid = |skip: Option<(usize, usize)>| {
+            let mut faces = Vec::new();
+            for i in 0..3 {
+                for j in 0..3 {
+                    if Some((i, j)) != skip {
+                        faces.push([v(i, j), v(i + 1, j), v(i + 1, j + 1)]);
+                        faces.push([v(i + 1, j + 1), v(i, j + 1), v(i, j)]);
+                    }
+                }
+            }
+            faces
+        };
+        let disk = Topology::new(&grid(None));
+        assert!(disk.defects.is_empty());
+        assert_eq!(disk.components, vec![(1, 1)]);
+        let annulus = Topology::new(&grid(Some((1, 1))));
+        assert!(annulus.defects.is_empty());
+        assert_eq!(annulus.components, vec![(0, 2)]);
+
+        // A torus with a hole has the Euler characteristic of an annulus, but one boundary loop
+        // less, and a tetrahedron with a hole is a disk.
+        let mut torus = Vec::new();
+        let v = |i: usize, j: usize| (i % 3) * 3 + j % 3;
+        for i in 0..3 {
+            for j in 0..3 {
+                torus.push([v(i, j), v(i + 1, j), v(i + 1, j + 1)]);
+                torus.push([v(i + 1, j + 1), v(i, j + 1), v(i, j)]);
+            }
+        }
+        torus.pop();
+        assert_eq!(Topology::new(&torus).components, vec![(-1, 1)]);
+        assert_eq!(Topology::new(&TETRAHEDRON[1..]).components, vec![(1, 1)]);
+    }
+
+    #[test]
+    fn defects() {
+        // Two tetrahedra sharing vertex 0.
+        let mut pinched = TETRAHEDRON.to_vec();
+        pinched.extend(offset(&TETRAHEDRON, 4).iter().map(|f| f.map(|v| v % 7)));
+        let pinched = Topology::new(&pinched);
+        assert_eq!(pinched.defects.into_iter().collect::<Vec<_>>(), vec![0]);
+
+        // A duplicate face.
+        let mut duplicate = TETRAHEDRON.to_vec();
+        duplicate.push(TETRAHEDRON[0]);
+        let duplicate = Topology::new(&duplicate);
+        assert_eq!(
+            duplicate.defects.into_iter().collect::<Vec<_>>(),
+            vec![0, 1, 2]
+        );
+
+        // A fin of three faces on one edge.
+        let fin = Topology::new(&[[0, 1, 2], [1, 0, 3], [0, 1, 4]]);
+        assert!(fin.defects.contains(&0) && fin.defects.contains(&1));
+    }
+
+    #[test]
+    fn long_strip() {
+        // Faces in strip order chain all vertices, which must not exhaust the stack.
+        let n = 100_000;
+        let strip: Vec<[usize; 3]> = (0..n)
+            .map(|i| {
+                if i % 2 == 0 {
+                    [i, i + 1, i + 2]
+                } else {
+                    [i + 1, i, i + 2]
+                }
+            })
+            .collect();
+        let strip = Topology::new(&strip);
+        assert!(strip.defects.is_empty());
+        assert_eq!(strip.components, vec![(1, 1)]);
+    }
+}